@@ -0,0 +1,266 @@
+//! A small command-line lexer.
+//!
+//! Splits raw arguments into short flags, long flags and plain values so that
+//! `Config` only has to decide what each flag means. Supports combined short
+//! flags (`-inv`), attached values (`-A3`, `--after-context=3`) and the `--`
+//! terminator.
+
+use std::error::Error;
+use std::fmt;
+
+/// A single lexed command-line argument.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Arg {
+    /// A short flag such as `-i`, without the dash.
+    Short(char),
+    /// A long flag such as `--regex`, without the dashes.
+    Long(String),
+    /// A positional argument, or anything after `--`.
+    Value(String),
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arg::Short(c) => write!(f, "-{}", c),
+            Arg::Long(name) => write!(f, "--{}", name),
+            Arg::Value(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Errors produced while turning the command line into a `Config`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgsError {
+    /// A flag that minigrep does not know about.
+    UnknownFlag(String),
+    /// A flag that needs a value was given none.
+    MissingValue(String),
+    /// A flag that takes no value was given one, e.g. `--flag=value`.
+    UnexpectedValue(String, String),
+    /// A flag was given a value it could not use.
+    InvalidValue {
+        flag: String,
+        value: String,
+        reason: String,
+    },
+    /// No query string was given.
+    MissingQuery,
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgsError::UnknownFlag(flag) => write!(f, "unknown flag '{}'", flag),
+            ArgsError::MissingValue(flag) => write!(f, "flag '{}' requires a value", flag),
+            ArgsError::UnexpectedValue(flag, value) => {
+                write!(f, "flag '{}' does not take a value (got '{}')", flag, value)
+            }
+            ArgsError::InvalidValue {
+                flag,
+                value,
+                reason,
+            } => write!(f, "invalid value '{}' for '{}': {}", value, flag, reason),
+            ArgsError::MissingQuery => write!(f, "Didn't get a query string"),
+        }
+    }
+}
+
+impl Error for ArgsError {}
+
+/// Lexes an iterator of arguments into `Arg`s.
+///
+/// The program name must already have been removed from `args`.
+pub struct Parser<I> {
+    args: I,
+    /// The unread rest of a combined short flag group such as `nv` in `-inv`.
+    shorts: Option<String>,
+    /// The value attached to the last long flag with `=`.
+    attached: Option<String>,
+    /// The last flag returned, used in error messages.
+    last_flag: String,
+    /// Set once `--` has been seen.
+    finished: bool,
+}
+
+impl<I> Parser<I>
+where
+    I: Iterator<Item = String>,
+{
+    pub fn new(args: I) -> Parser<I> {
+        Parser {
+            args,
+            shorts: None,
+            attached: None,
+            last_flag: String::new(),
+            finished: false,
+        }
+    }
+
+    /// Returns the next argument, or `None` once all arguments are consumed.
+    ///
+    /// Fails if the previous long flag had an `=value` that nobody asked for.
+    pub fn next_arg(&mut self) -> Result<Option<Arg>, ArgsError> {
+        if let Some(value) = self.attached.take() {
+            return Err(ArgsError::UnexpectedValue(self.last_flag.clone(), value));
+        }
+
+        if let Some(rest) = self.shorts.take() {
+            return Ok(Some(self.short(rest)));
+        }
+
+        let arg = match self.args.next() {
+            Some(arg) => arg,
+            None => return Ok(None),
+        };
+
+        if self.finished {
+            return Ok(Some(Arg::Value(arg)));
+        }
+
+        if arg == "--" {
+            self.finished = true;
+            return self.next_arg();
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let name = match long.find('=') {
+                Some(eq) => {
+                    self.attached = Some(long[eq + 1..].to_string());
+                    &long[..eq]
+                }
+                None => long,
+            };
+            self.last_flag = format!("--{}", name);
+            return Ok(Some(Arg::Long(name.to_string())));
+        }
+
+        if arg.len() > 1 && arg.starts_with('-') {
+            return Ok(Some(self.short(arg[1..].to_string())));
+        }
+
+        Ok(Some(Arg::Value(arg)))
+    }
+
+    /// Returns the value for the flag that was just returned by `next_arg`.
+    ///
+    /// The value is taken from, in order: an attached `--flag=value`, the rest
+    /// of a short flag group (`-A3`), or the following argument.
+    pub fn value(&mut self) -> Result<String, ArgsError> {
+        if let Some(value) = self.attached.take() {
+            return Ok(value);
+        }
+
+        if let Some(rest) = self.shorts.take() {
+            return Ok(rest);
+        }
+
+        self.args
+            .next()
+            .ok_or_else(|| ArgsError::MissingValue(self.last_flag.clone()))
+    }
+
+    /// Returns the value for the last flag parsed as a number.
+    pub fn number<T>(&mut self) -> Result<T, ArgsError>
+    where
+        T: std::str::FromStr,
+        T::Err: fmt::Display,
+    {
+        let value = self.value()?;
+        value.parse().map_err(|e: T::Err| ArgsError::InvalidValue {
+            flag: self.last_flag.clone(),
+            value,
+            reason: e.to_string(),
+        })
+    }
+
+    /// Builds an `InvalidValue` error for the last flag.
+    pub fn invalid(&self, value: String, reason: &str) -> ArgsError {
+        ArgsError::InvalidValue {
+            flag: self.last_flag.clone(),
+            value,
+            reason: reason.to_string(),
+        }
+    }
+
+    fn short(&mut self, mut group: String) -> Arg {
+        let c = group.remove(0);
+        if !group.is_empty() {
+            self.shorts = Some(group);
+        }
+        self.last_flag = format!("-{}", c);
+        Arg::Short(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(args: &[&str]) -> Result<Vec<Arg>, ArgsError> {
+        let mut parser = Parser::new(args.iter().map(|s| s.to_string()));
+        let mut out = Vec::new();
+        while let Some(arg) = parser.next_arg()? {
+            out.push(arg);
+        }
+        Ok(out)
+    }
+
+    #[test]
+    fn combined_short_flags() {
+        assert_eq!(
+            vec![Arg::Short('i'), Arg::Short('n'), Arg::Short('v')],
+            lex(&["-inv"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn terminator_and_dash() {
+        assert_eq!(
+            vec![
+                Arg::Value("-".to_string()),
+                Arg::Value("-v".to_string()),
+                Arg::Value("--x".to_string()),
+            ],
+            lex(&["-", "--", "-v", "--x"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn attached_values() {
+        let args = ["-A3", "--color=never", "-e", "-x"];
+        let mut parser = Parser::new(args.iter().map(|s| s.to_string()));
+
+        assert_eq!(Some(Arg::Short('A')), parser.next_arg().unwrap());
+        assert_eq!("3", parser.value().unwrap());
+        assert_eq!(
+            Some(Arg::Long("color".to_string())),
+            parser.next_arg().unwrap()
+        );
+        assert_eq!("never", parser.value().unwrap());
+        assert_eq!(Some(Arg::Short('e')), parser.next_arg().unwrap());
+        assert_eq!("-x", parser.value().unwrap());
+        assert_eq!(None, parser.next_arg().unwrap());
+    }
+
+    #[test]
+    fn unused_attached_value() {
+        assert_eq!(
+            Err(ArgsError::UnexpectedValue(
+                "--regex".to_string(),
+                "yes".to_string()
+            )),
+            lex(&["--regex=yes"])
+        );
+    }
+
+    #[test]
+    fn missing_value() {
+        let mut parser = Parser::new(vec!["--max-count".to_string()].into_iter());
+        parser.next_arg().unwrap();
+        assert_eq!(
+            Err(ArgsError::MissingValue("--max-count".to_string())),
+            parser.value()
+        );
+    }
+}
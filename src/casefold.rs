@@ -0,0 +1,305 @@
+//! Case-insensitive matching with Unicode full case folding.
+//!
+//! Folding maps every character to a canonical caseless form, which may be
+//! longer than the original: `ß` and `ẞ` fold to `ss`, `İ` folds to `i̇` and
+//! `ﬃ` folds to `ffi`. Two strings match case insensitively when their
+//! foldings are equal, so `STRASSE` matches `straße`.
+//!
+//! The folding is derived from the standard library's full case mappings, by
+//! lowercasing the uppercase form of each character. This agrees with the
+//! default (non-Turkic) folding of Unicode's `CaseFolding.txt`, with two
+//! special cases: the dotless `ı` does not fold to `i`, and the capital
+//! sharp `ẞ` folds all the way to `ss`.
+
+use std::ops::Range;
+use std::str;
+
+use memchr::{memchr, memchr2, memchr3};
+
+/// The folded form of a single character: at most three characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Folded {
+    chars: [char; 3],
+    len: usize,
+}
+
+impl Folded {
+    pub fn as_slice(&self) -> &[char] {
+        &self.chars[..self.len]
+    }
+}
+
+/// Folds a single character.
+pub fn fold(c: char) -> Folded {
+    let mut folded = Folded {
+        chars: ['\0'; 3],
+        len: 0,
+    };
+    let mut push = |c| {
+        folded.chars[folded.len] = c;
+        folded.len += 1;
+    };
+
+    if c.is_ascii() {
+        push(c.to_ascii_lowercase());
+    } else if c == '\u{131}' {
+        push(c);
+    } else if c == '\u{1e9e}' {
+        push('s');
+        push('s');
+    } else {
+        c.to_uppercase()
+            .flat_map(char::to_lowercase)
+            .for_each(&mut push);
+    }
+
+    folded
+}
+
+/// Folds every character of `bytes`, replacing invalid UTF-8 with `U+FFFD`.
+pub fn fold_bytes(mut bytes: &[u8]) -> String {
+    let mut folded = String::with_capacity(bytes.len());
+    while !bytes.is_empty() {
+        let (c, width) = decode(bytes);
+        folded.extend(fold(c.unwrap_or(char::REPLACEMENT_CHARACTER)).as_slice());
+        bytes = &bytes[width..];
+    }
+    folded
+}
+
+/// Pairs of a folded character and another character whose folding starts
+/// with it, where the other character's first byte is neither that of the
+/// folded character nor that of its uppercase form: `k` and the Kelvin sign,
+/// or `s` and `ß`. One pair is listed for each such first byte, and the
+/// `first_bytes_cover_every_fold` test checks the table against all of
+/// Unicode.
+const OTHER_FOLDS: &[(char, char)] = &[
+    ('a', '\u{1e9a}'),
+    ('f', '\u{fb00}'),
+    ('h', '\u{1e96}'),
+    ('i', '\u{130}'),
+    ('j', '\u{1f0}'),
+    ('k', '\u{212a}'),
+    ('s', '\u{df}'),
+    ('s', '\u{17f}'),
+    ('s', '\u{1e9e}'),
+    ('s', '\u{fb05}'),
+    ('t', '\u{1e97}'),
+    ('w', '\u{1e98}'),
+    ('y', '\u{1e99}'),
+    ('\u{e5}', '\u{212b}'),
+    ('\u{2bc}', '\u{149}'),
+    ('\u{3ac}', '\u{1fb4}'),
+    ('\u{3ae}', '\u{1fc4}'),
+    ('\u{3b1}', '\u{1fb3}'),
+    ('\u{3b2}', '\u{3d0}'),
+    ('\u{3b5}', '\u{3f5}'),
+    ('\u{3b7}', '\u{1fc3}'),
+    ('\u{3b8}', '\u{3d1}'),
+    ('\u{3b9}', '\u{345}'),
+    ('\u{3b9}', '\u{1fbe}'),
+    ('\u{3ba}', '\u{3f0}'),
+    ('\u{3bc}', '\u{b5}'),
+    ('\u{3c1}', '\u{1fe4}'),
+    ('\u{3c5}', '\u{1f50}'),
+    ('\u{3c9}', '\u{1ff3}'),
+    ('\u{3c9}', '\u{2126}'),
+    ('\u{3ce}', '\u{1ff4}'),
+    ('\u{432}', '\u{1c80}'),
+    ('\u{434}', '\u{1c81}'),
+    ('\u{43e}', '\u{1c82}'),
+    ('\u{441}', '\u{1c83}'),
+    ('\u{442}', '\u{1c84}'),
+    ('\u{44a}', '\u{1c86}'),
+    ('\u{463}', '\u{1c87}'),
+    ('\u{565}', '\u{587}'),
+    ('\u{574}', '\u{fb13}'),
+    ('\u{57e}', '\u{fb16}'),
+    ('\u{a64b}', '\u{1c88}'),
+];
+
+/// Returns the first bytes of the characters whose folding starts with `c`,
+/// sorted.
+fn first_bytes(c: char) -> Vec<u8> {
+    let upper = c.to_uppercase().next().unwrap_or(c);
+    let mut bytes: Vec<_> = OTHER_FOLDS
+        .iter()
+        .filter(|&&(folded, _)| folded == c)
+        .map(|&(_, other)| other)
+        .chain([c, upper])
+        .map(first_byte)
+        .collect();
+    bytes.sort_unstable();
+    bytes.dedup();
+    bytes
+}
+
+fn first_byte(c: char) -> u8 {
+    c.encode_utf8(&mut [0; 4]).as_bytes()[0]
+}
+
+/// A query compiled for case-insensitive search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseFolder {
+    folded: Vec<char>,
+    /// The first bytes a match can start with, sorted.
+    first_bytes: Vec<u8>,
+}
+
+impl CaseFolder {
+    pub fn new(query: &str) -> CaseFolder {
+        let mut folded = Vec::with_capacity(query.len());
+        for c in query.chars() {
+            folded.extend_from_slice(fold(c).as_slice());
+        }
+
+        let first_bytes = folded.first().map_or_else(Vec::new, |&c| first_bytes(c));
+        CaseFolder {
+            folded,
+            first_bytes,
+        }
+    }
+
+    /// Finds the first match in `haystack` that starts at or after `start`.
+    ///
+    /// The returned range is in bytes of `haystack` and always covers whole
+    /// characters: a match must start and end on character boundaries, so the
+    /// query `s` does not match half of `ß`. Bytes that are not valid UTF-8
+    /// never match. Nothing is allocated.
+    ///
+    /// Only the positions holding a byte that starts a character folding to
+    /// the start of the query are tried, found with `memchr` when there are
+    /// at most three such bytes.
+    pub fn find_at(&self, haystack: &[u8], start: usize) -> Option<Range<usize>> {
+        if self.folded.is_empty() {
+            return (start <= haystack.len()).then_some(start..start);
+        }
+
+        let mut at = start;
+        while at < haystack.len() {
+            at += self.next_candidate(&haystack[at..])?;
+            if let Some(end) = self.match_len(&haystack[at..]) {
+                return Some(at..at + end);
+            }
+            at += 1;
+        }
+        None
+    }
+
+    /// Returns where a match starting exactly at `at` in `haystack` ends.
+    pub fn match_at(&self, haystack: &[u8], at: usize) -> Option<usize> {
+        self.match_len(&haystack[at..]).map(|len| at + len)
+    }
+
+    /// Returns the position of the first byte in `haystack` that a match can
+    /// start with.
+    fn next_candidate(&self, haystack: &[u8]) -> Option<usize> {
+        match *self.first_bytes.as_slice() {
+            [a] => memchr(a, haystack),
+            [a, b] => memchr2(a, b, haystack),
+            [a, b, c] => memchr3(a, b, c, haystack),
+            _ => haystack
+                .iter()
+                .position(|b| self.first_bytes.binary_search(b).is_ok()),
+        }
+    }
+
+    /// Returns the length in bytes of a match at the very start of `haystack`.
+    fn match_len(&self, haystack: &[u8]) -> Option<usize> {
+        let mut query = self.folded.as_slice();
+        let mut at = 0;
+
+        while !query.is_empty() {
+            let (c, width) = decode(&haystack[at..]);
+            let folded = fold(c?);
+            let folded = folded.as_slice();
+            // Either the query ends partway through the folded character,
+            // which is not a match, or they must agree on the whole of it.
+            if folded.len() > query.len() || !query.starts_with(folded) {
+                return None;
+            }
+            query = &query[folded.len()..];
+            at += width;
+        }
+
+        Some(at)
+    }
+}
+
+/// Decodes the character at the start of `bytes`, with its width in bytes.
+///
+/// Returns `None` with a width of 1 for an invalid or truncated sequence, and
+/// `None` with a width of 0 at the end of the input.
+fn decode(bytes: &[u8]) -> (Option<char>, usize) {
+    let first = match bytes.first() {
+        Some(&b) => b,
+        None => return (None, 0),
+    };
+    let width = match first {
+        0x00..=0x7f => return (Some(first as char), 1),
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return (None, 1),
+    };
+
+    match bytes.get(..width).map(str::from_utf8) {
+        Some(Ok(s)) => (s.chars().next(), width),
+        _ => (None, 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(query: &str, haystack: &str) -> Option<Range<usize>> {
+        CaseFolder::new(query).find_at(haystack.as_bytes(), 0)
+    }
+
+    #[test]
+    fn folds_characters() {
+        assert_eq!(&['s', 's'], fold('ß').as_slice());
+        assert_eq!(&['s', 's'], fold('ẞ').as_slice());
+        assert_eq!(&['i', '\u{307}'], fold('İ').as_slice());
+        assert_eq!(&['ı'], fold('ı').as_slice());
+        assert_eq!(&['σ'], fold('ς').as_slice());
+        assert_eq!(&['k'], fold('\u{212a}').as_slice());
+        assert_eq!("die strasse\u{fffd}", fold_bytes(b"Die Stra\xc3\x9fe\xff"));
+    }
+
+    #[test]
+    fn length_changing_matches() {
+        assert_eq!(Some(0..7), find("straße", "STRASSE"));
+        assert_eq!(Some(4..11), find("STRASSE", "die straße"));
+        assert_eq!(Some(0..3), find("ffi", "ﬃ"));
+        assert_eq!(None, find("s", "ß"));
+        assert_eq!(Some(1..3), find("i\u{307}", "xİ"));
+        assert_eq!(None, find("i", "ı"));
+    }
+
+    #[test]
+    fn offsets_are_in_original_bytes() {
+        assert_eq!(Some(3..5), find("é", "caf\u{c9}"));
+        assert_eq!(Some(2..6), find("duct", "\u{e9}DUCT"));
+        assert_eq!(Some(2..4), CaseFolder::new("ab").find_at(b"\xff\xffAB", 0));
+        assert_eq!(Some(0..0), find("", "abc"));
+    }
+
+    #[test]
+    fn first_bytes_cover_every_fold() {
+        for c in (0..=char::MAX as u32).filter_map(char::from_u32) {
+            let folded = fold(c).as_slice()[0];
+            assert!(first_bytes(folded).contains(&first_byte(c)), "{:?}", c);
+        }
+    }
+
+    #[test]
+    fn candidates_include_other_folds() {
+        assert_eq!(Some(2..7), find("key", "a \u{212a}EY"));
+        assert_eq!(Some(0..3), find("ss", "ẞ"));
+        assert_eq!(Some(3..5), find("s", "ab ſ"));
+        assert_eq!(Some(6..7), find("s", "ß ß S"));
+        assert_eq!(None, find("é", "cafe"));
+    }
+}
@@ -0,0 +1,139 @@
+//! ANSI colors for terminal output.
+
+use std::env;
+use std::str::FromStr;
+
+/// When to color the output, as chosen with `--color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Color only when standard output is a terminal.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Returns whether to color, given whether the output is a terminal.
+    pub fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Auto => is_terminal,
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<ColorChoice, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err("expected 'auto', 'always' or 'never'"),
+        }
+    }
+}
+
+/// SGR parameters for each part of an output line, such as `01;31`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Colors {
+    /// Matched text.
+    pub matched: String,
+    /// File names.
+    pub path: String,
+    /// Line numbers and columns.
+    pub line_number: String,
+    /// Byte offsets.
+    pub byte_offset: String,
+    /// The `:`, `-` and `--` separators.
+    pub separator: String,
+}
+
+impl Default for Colors {
+    /// The same colors as grep.
+    fn default() -> Colors {
+        Colors {
+            matched: "01;31".to_string(),
+            path: "35".to_string(),
+            line_number: "32".to_string(),
+            byte_offset: "32".to_string(),
+            separator: "36".to_string(),
+        }
+    }
+}
+
+impl Colors {
+    /// Reads the colors from the `MINIGREP_COLORS` environment variable,
+    /// falling back to the defaults.
+    pub fn from_env() -> Colors {
+        match env::var("MINIGREP_COLORS") {
+            Ok(spec) => Colors::parse(&spec),
+            Err(_) => Colors::default(),
+        }
+    }
+
+    /// Parses a `GREP_COLORS`-style specification such as `mt=01;32:fn=34`.
+    ///
+    /// The keys are `mt` (or `ms`) for matches, `fn` for file names, `ln` for
+    /// line numbers, `bn` for byte offsets and `se` for separators. Unknown
+    /// keys and values that are not SGR parameters are ignored.
+    pub fn parse(spec: &str) -> Colors {
+        let mut colors = Colors::default();
+
+        for entry in spec.split(':') {
+            let (key, value) = match entry.split_once('=') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            if !value.bytes().all(|b| b.is_ascii_digit() || b == b';') {
+                continue;
+            }
+
+            let field = match key {
+                "mt" | "ms" => &mut colors.matched,
+                "fn" => &mut colors.path,
+                "ln" => &mut colors.line_number,
+                "bn" => &mut colors.byte_offset,
+                "se" => &mut colors.separator,
+                _ => continue,
+            };
+            *field = value.to_string();
+        }
+
+        colors
+    }
+}
+
+/// Returns the escape sequence starting text in the given SGR color.
+pub fn start(sgr: &str) -> String {
+    format!("\x1b[{}m\x1b[K", sgr)
+}
+
+/// The escape sequence resetting the color.
+pub const END: &str = "\x1b[m\x1b[K";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_spec() {
+        let colors = Colors::parse("ms=01;32:fn=34:xx=1:ln=bold:se");
+
+        assert_eq!("01;32", colors.matched);
+        assert_eq!("34", colors.path);
+        assert_eq!("32", colors.line_number);
+        assert_eq!("36", colors.separator);
+    }
+
+    #[test]
+    fn choice() {
+        assert_eq!(Ok(ColorChoice::Never), "never".parse());
+        assert!("sometimes".parse::<ColorChoice>().is_err());
+        assert!(ColorChoice::Auto.enabled(true));
+        assert!(!ColorChoice::Auto.enabled(false));
+    }
+}
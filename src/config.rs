@@ -0,0 +1,717 @@
+use std::env;
+use std::fs;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+
+use crate::args::{Arg, ArgsError, Parser};
+use crate::color::ColorChoice;
+use crate::glob::Glob;
+use crate::types::FileTypes;
+
+#[derive(Debug, Default)]
+pub struct Config {
+    /// The patterns to search for; a line matches if any of them does.
+    pub patterns: Vec<String>,
+    /// Files, directories and glob patterns to search; `-` is standard input.
+    pub paths: Vec<String>,
+    /// Whether matching is case sensitive, once flags, smart case and the
+    /// `CASE_INSENSITIVE` environment variable have been taken into account.
+    pub case_sensitive: bool,
+    /// Treat the patterns as regular expressions instead of literal strings.
+    pub regex: bool,
+    /// Treat the patterns as literal strings, searching whole blocks of the
+    /// input at once instead of line by line. The last of `-E` and `-F` wins.
+    pub fixed_strings: bool,
+    /// Only match whole words: a match must not be preceded or followed by a
+    /// word character.
+    pub word_regexp: bool,
+    /// Only match whole lines; wins over `word_regexp`.
+    pub line_regexp: bool,
+    /// How deep to descend when a path is a directory; `None` is unlimited.
+    pub max_depth: Option<usize>,
+    /// Follow symbolic links while walking directories.
+    pub follow: bool,
+    /// Skip hidden files and directories while walking directories; on
+    /// unless `--hidden` is given.
+    pub skip_hidden: bool,
+    /// Search the files listed in ignore files while walking directories,
+    /// instead of skipping them.
+    pub no_ignore: bool,
+    /// Only search the files matching one of these globs, if any.
+    pub include: Vec<Glob>,
+    /// Skip the files matching one of these globs.
+    pub exclude: Vec<Glob>,
+    /// Skip the directories matching one of these globs.
+    pub exclude_dir: Vec<Glob>,
+    /// The file types known to `-t` and `-T`: the built-in ones, with those
+    /// added by `--type-add`.
+    pub file_types: FileTypes,
+    /// Only search the files of one of the types given with `-t`, if any; the
+    /// globs of these types.
+    pub types: Vec<Glob>,
+    /// Skip the files of the types given with `-T`; the globs of these types.
+    pub types_not: Vec<Glob>,
+    /// Print the file types instead of searching; no pattern is needed.
+    pub type_list: bool,
+    /// Print the file name with each line: `-H` forces it on, `-h` forces it off,
+    /// and `None` prints it when more than one file may be searched.
+    pub with_filename: Option<bool>,
+    /// Prefix each line with its line number.
+    pub line_number: bool,
+    /// Prefix each line with the column of its first match; implies `line_number`.
+    pub column: bool,
+    /// Prefix each line with its byte offset in the input.
+    pub byte_offset: bool,
+    /// How many lines to print before each match.
+    pub before_context: usize,
+    /// How many lines to print after each match.
+    pub after_context: usize,
+    /// Select the lines that do not match any pattern.
+    pub invert_match: bool,
+    /// Stop reading each input after this many matching lines.
+    pub max_count: Option<u64>,
+    /// Stop searching after this many matching lines across all inputs.
+    pub max_total: Option<u64>,
+    /// When to highlight matches with ANSI colors.
+    pub color: ColorChoice,
+    /// What to print for each input.
+    pub output: OutputMode,
+    /// Follow each printed file name with a NUL byte instead of `:` or a newline.
+    pub null: bool,
+    /// Print only the matched parts of each line, one per line.
+    pub only_matching: bool,
+    /// How many files to search at once; `None` is one per CPU.
+    pub jobs: Option<usize>,
+    /// The order in which the results of each file are printed.
+    pub sort: SortMode,
+    /// Print every event of the search as JSON Lines, instead of grep's
+    /// format; output modes are ignored.
+    pub json: bool,
+    /// Print nothing, stopping at the first match; only the exit code tells
+    /// whether anything matched.
+    pub quiet: bool,
+    /// Do not report inputs that cannot be read; they still make the exit
+    /// code 2.
+    pub no_messages: bool,
+    /// With `only_matching`, print this capture group, by number or name,
+    /// instead of the whole match. Implies `only_matching`.
+    pub group: Option<String>,
+}
+
+/// What to print for each input, as chosen with `-c`, `-l` and `-L`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMode {
+    /// The matching lines.
+    #[default]
+    Lines,
+    /// How many lines match.
+    Count,
+    /// The path of the input, if any line matches.
+    FilesWithMatches,
+    /// The path of the input, if no line matches.
+    FilesWithoutMatch,
+}
+
+/// The order of the results of each file, as chosen with `--sort`.
+///
+/// Files searched in parallel are printed whole, one after the other, but in
+/// the order in which they are done unless sorted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortMode {
+    /// Whichever file is done first.
+    #[default]
+    None,
+    /// The order of the paths on the command line, with the files of each
+    /// directory sorted by path, as when searching one file at a time.
+    Path,
+}
+
+impl FromStr for SortMode {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<SortMode, Self::Err> {
+        match s {
+            "none" => Ok(SortMode::None),
+            "path" => Ok(SortMode::Path),
+            _ => Err("expected 'none' or 'path'"),
+        }
+    }
+}
+
+/// How case is treated, as chosen on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CaseMode {
+    Sensitive,
+    Insensitive,
+    /// Insensitive unless a pattern has an uppercase letter.
+    Smart,
+}
+
+impl CaseMode {
+    /// Picks the mode to use. The last case flag given wins; without one,
+    /// setting the `CASE_INSENSITIVE` environment variable makes the search
+    /// case insensitive.
+    fn resolve(flag: Option<CaseMode>, env_insensitive: bool) -> CaseMode {
+        match flag {
+            Some(mode) => mode,
+            None if env_insensitive => CaseMode::Insensitive,
+            None => CaseMode::Sensitive,
+        }
+    }
+
+    /// Decides whether the `patterns` are matched case sensitively. With
+    /// `regex`, escapes such as `\S` or `\p{Lu}` do not count as uppercase
+    /// letters for smart case.
+    fn is_case_sensitive(self, patterns: &[String], regex: bool) -> bool {
+        match self {
+            CaseMode::Sensitive => true,
+            CaseMode::Insensitive => false,
+            CaseMode::Smart => patterns.iter().any(|pattern| has_uppercase(pattern, regex)),
+        }
+    }
+}
+
+/// Returns whether a pattern has an uppercase letter, leaving out the
+/// escapes of a regular expression: the character after a `\`, with the
+/// class name or code point after `\p`, `\P`, `\x`, `\u` and `\U`.
+fn has_uppercase(pattern: &str, regex: bool) -> bool {
+    if !regex {
+        return pattern.chars().any(char::is_uppercase);
+    }
+
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            if c.is_uppercase() {
+                return true;
+            }
+            continue;
+        }
+
+        // Without braces, how many characters follow the escape: a one-letter
+        // class as in `\pL`, or hex digits as in `\xFF`.
+        let length = match chars.next() {
+            Some('p' | 'P') => 1,
+            Some('x') => 2,
+            Some('u') => 4,
+            Some('U') => 8,
+            _ => continue,
+        };
+        if chars.clone().next() == Some('{') {
+            chars.by_ref().find(|&c| c == '}');
+        } else {
+            chars.nth(length - 1);
+        }
+    }
+    false
+}
+
+/// Short flags and the long flag each one is an alias for.
+const SHORT_FLAGS: &[(char, &str)] = &[
+    ('E', "regex"),
+    ('F', "fixed-strings"),
+    ('e', "regexp"),
+    ('f', "file"),
+    ('w', "word-regexp"),
+    ('x', "line-regexp"),
+    ('R', "follow"),
+    ('H', "with-filename"),
+    ('h', "no-filename"),
+    ('n', "line-number"),
+    ('b', "byte-offset"),
+    ('A', "after-context"),
+    ('B', "before-context"),
+    ('C', "context"),
+    ('v', "invert-match"),
+    ('m', "max-count"),
+    ('i', "ignore-case"),
+    ('S', "smart-case"),
+    ('c', "count"),
+    ('l', "files-with-matches"),
+    ('L', "files-without-match"),
+    ('Z', "null"),
+    ('o', "only-matching"),
+    ('q', "quiet"),
+    ('j', "threads"),
+    ('s', "no-messages"),
+    ('t', "type"),
+    ('T', "type-not"),
+];
+
+impl Config {
+    /// Builds a `Config` from the command-line arguments, including the
+    /// program name in first position.
+    ///
+    /// Flags and positional arguments may be mixed in any order; everything
+    /// after `--` is treated as positional. Patterns are given with `-e` or
+    /// read from a file with `-f`, one per line; without either, the first
+    /// positional argument is the pattern. The remaining positional arguments
+    /// are the paths to search. Standard input is searched when no path is
+    /// given.
+    pub fn new<I>(args: I) -> Result<Config, ArgsError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut parser = Parser::new(args.into_iter().skip(1));
+        let mut config = Config {
+            skip_hidden: true,
+            ..Config::default()
+        };
+        let mut positional = Vec::new();
+        // `-A` and `-B` win over `-C`, whatever their order.
+        let (mut after, mut before, mut context) = (None, None, None);
+        let mut case_mode = None;
+        let mut has_patterns = false;
+        // Type names are looked up once all `--type-add` flags are known.
+        let (mut types, mut types_not) = (Vec::new(), Vec::new());
+
+        while let Some(arg) = parser.next_arg()? {
+            let name = match long_name(&arg) {
+                Some(name) => name,
+                None => {
+                    if let Arg::Value(value) = arg {
+                        positional.push(value);
+                        continue;
+                    }
+                    return Err(ArgsError::UnknownFlag(arg.to_string()));
+                }
+            };
+
+            match name {
+                "regex" => {
+                    config.regex = true;
+                    config.fixed_strings = false;
+                }
+                "fixed-strings" => {
+                    config.fixed_strings = true;
+                    config.regex = false;
+                }
+                "word-regexp" => config.word_regexp = true,
+                "line-regexp" => config.line_regexp = true,
+                "regexp" => {
+                    config.patterns.push(parser.value()?);
+                    has_patterns = true;
+                }
+                "file" => {
+                    let path = parser.value()?;
+                    let contents = match fs::read_to_string(&path) {
+                        Ok(contents) => contents,
+                        Err(e) => return Err(parser.invalid(path, &e.to_string())),
+                    };
+                    config.patterns.extend(contents.lines().map(String::from));
+                    has_patterns = true;
+                }
+                "max-depth" => config.max_depth = Some(parser.number()?),
+                "follow" => config.follow = true,
+                "hidden" => config.skip_hidden = false,
+                "no-hidden" => config.skip_hidden = true,
+                "no-ignore" => config.no_ignore = true,
+                "include" => config.include.push(glob(&mut parser)?),
+                "exclude" => config.exclude.push(glob(&mut parser)?),
+                "exclude-dir" => config.exclude_dir.push(glob(&mut parser)?),
+                "type" => types.push(parser.value()?),
+                "type-not" => types_not.push(parser.value()?),
+                "type-add" => {
+                    let value = parser.value()?;
+                    if let Err(e) = config.file_types.add(&value) {
+                        return Err(parser.invalid(value, &e));
+                    }
+                }
+                "type-list" => config.type_list = true,
+                "with-filename" => config.with_filename = Some(true),
+                "no-filename" => config.with_filename = Some(false),
+                "line-number" => config.line_number = true,
+                "column" => {
+                    config.column = true;
+                    config.line_number = true;
+                }
+                "byte-offset" => config.byte_offset = true,
+                "after-context" => after = Some(parser.number()?),
+                "before-context" => before = Some(parser.number()?),
+                "context" => context = Some(parser.number()?),
+                "invert-match" => config.invert_match = true,
+                "max-count" => config.max_count = Some(parser.number()?),
+                "max-total" => config.max_total = Some(parser.number()?),
+                "ignore-case" => case_mode = Some(CaseMode::Insensitive),
+                "case-sensitive" => case_mode = Some(CaseMode::Sensitive),
+                "smart-case" => case_mode = Some(CaseMode::Smart),
+                "count" => config.output = OutputMode::Count,
+                "files-with-matches" => config.output = OutputMode::FilesWithMatches,
+                "files-without-match" => config.output = OutputMode::FilesWithoutMatch,
+                "null" => config.null = true,
+                "only-matching" => config.only_matching = true,
+                "threads" => config.jobs = Some(parser.number::<NonZeroUsize>()?.get()),
+                "sort" => {
+                    let value = parser.value()?;
+                    config.sort = value.parse().map_err(|e| parser.invalid(value, e))?;
+                }
+                "json" => config.json = true,
+                "quiet" | "silent" => config.quiet = true,
+                "no-messages" => config.no_messages = true,
+                "group" => {
+                    config.group = Some(parser.value()?);
+                    config.only_matching = true;
+                }
+                "color" | "colour" => {
+                    let value = parser.value()?;
+                    config.color = value.parse().map_err(|e| parser.invalid(value, e))?;
+                }
+                _ => return Err(ArgsError::UnknownFlag(arg.to_string())),
+            }
+        }
+
+        config.types = type_globs(&config.file_types, types, "--type")?;
+        config.types_not = type_globs(&config.file_types, types_not, "--type-not")?;
+        config.after_context = after.or(context).unwrap_or(0);
+        config.before_context = before.or(context).unwrap_or(0);
+
+        let mut positional = positional.into_iter();
+
+        if !has_patterns && !config.type_list {
+            match positional.next() {
+                Some(arg) => config.patterns.push(arg),
+                None => return Err(ArgsError::MissingQuery),
+            }
+        }
+
+        config.paths = positional.collect();
+        if config.paths.is_empty() {
+            config.paths.push("-".to_string());
+        }
+
+        let env_insensitive = env::var_os("CASE_INSENSITIVE").is_some();
+        config.case_sensitive = CaseMode::resolve(case_mode, env_insensitive)
+            .is_case_sensitive(&config.patterns, config.regex);
+
+        Ok(config)
+    }
+}
+
+/// Returns the value of the last flag, parsed as a glob.
+fn glob<I>(parser: &mut Parser<I>) -> Result<Glob, ArgsError>
+where
+    I: Iterator<Item = String>,
+{
+    let value = parser.value()?;
+    Glob::new(&value).map_err(|e| parser.invalid(value.clone(), e.reason()))
+}
+
+/// Returns the globs of the types `names`, given with `flag`.
+fn type_globs(types: &FileTypes, names: Vec<String>, flag: &str) -> Result<Vec<Glob>, ArgsError> {
+    let mut globs = Vec::new();
+    for name in names {
+        match types.globs(&name) {
+            Some(type_globs) => globs.extend_from_slice(type_globs),
+            None => {
+                return Err(ArgsError::InvalidValue {
+                    flag: flag.to_string(),
+                    value: name,
+                    reason: "unknown file type, see --type-list".to_string(),
+                })
+            }
+        }
+    }
+    Ok(globs)
+}
+
+/// Returns the long name of a flag, resolving short aliases.
+fn long_name(arg: &Arg) -> Option<&str> {
+    match arg {
+        Arg::Long(name) => Some(name),
+        Arg::Short(c) => SHORT_FLAGS
+            .iter()
+            .find(|(short, _)| short == c)
+            .map(|(_, long)| *long),
+        Arg::Value(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Config, ArgsError> {
+        let args = std::iter::once("minigrep").chain(args.iter().copied());
+        Config::new(args.map(String::from))
+    }
+
+    #[test]
+    fn positional_arguments() {
+        let config = parse(&["to", "poem.txt"]).unwrap();
+
+        assert_eq!(vec!["to"], config.patterns);
+        assert_eq!(vec!["poem.txt"], config.paths);
+    }
+
+    #[test]
+    fn terminator_allows_dash_query() {
+        let config = parse(&["--", "-to", "poem.txt"]).unwrap();
+
+        assert_eq!(vec!["-to"], config.patterns);
+    }
+
+    #[test]
+    fn pattern_flags() {
+        let config = parse(&["-e", "to", "--regexp=be", "poem.txt"]).unwrap();
+        assert_eq!(vec!["to", "be"], config.patterns);
+        assert_eq!(vec!["poem.txt"], config.paths);
+
+        let path = env::temp_dir().join(format!("minigrep-patterns-{}", std::process::id()));
+        fs::write(&path, "to\r\nbe\n\nor\n").unwrap();
+        let config = parse(&["-f", path.to_str().unwrap(), "-e", "not"]).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(vec!["to", "be", "", "or", "not"], config.patterns);
+        assert_eq!(vec!["-"], config.paths);
+
+        assert!(parse(&["-f", "/nonexistent/patterns", "poem.txt"]).is_err());
+    }
+
+    #[test]
+    fn regex_flag() {
+        assert!(!parse(&["to", "poem.txt"]).unwrap().regex);
+        assert!(parse(&["-E", "to", "poem.txt"]).unwrap().regex);
+        assert!(parse(&["to", "poem.txt", "--regex"]).unwrap().regex);
+
+        let config = parse(&["-E", "-F", "t.", "poem.txt"]).unwrap();
+        assert!(config.fixed_strings && !config.regex);
+        let config = parse(&["--fixed-strings", "-E", "t.", "poem.txt"]).unwrap();
+        assert!(config.regex && !config.fixed_strings);
+    }
+
+    #[test]
+    fn boundary_flags() {
+        let config = parse(&["-w", "to"]).unwrap();
+        assert!(config.word_regexp && !config.line_regexp);
+
+        let config = parse(&["--line-regexp", "to"]).unwrap();
+        assert!(config.line_regexp && !config.word_regexp);
+    }
+
+    #[test]
+    fn walk_flags() {
+        let config = parse(&["--max-depth=2", "-R", "to", "."]).unwrap();
+
+        assert_eq!(Some(2), config.max_depth);
+        assert!(config.follow);
+        assert!(config.skip_hidden && !config.no_ignore);
+
+        let config = parse(&["--hidden", "--no-ignore", "to", "."]).unwrap();
+        assert!(!config.skip_hidden && config.no_ignore);
+        assert!(
+            parse(&["--hidden", "--no-hidden", "to"])
+                .unwrap()
+                .skip_hidden
+        );
+    }
+
+    #[test]
+    fn filter_flags() {
+        let config = parse(&[
+            "--include=*.rs",
+            "--include",
+            "*.{md,toml}",
+            "--exclude=build.rs",
+            "--exclude-dir",
+            "target",
+            "to",
+        ])
+        .unwrap();
+
+        fn globs(globs: &[Glob]) -> Vec<&str> {
+            globs.iter().map(Glob::as_str).collect()
+        }
+        assert_eq!(vec!["*.rs", "*.{md,toml}"], globs(&config.include));
+        assert_eq!(vec!["build.rs"], globs(&config.exclude));
+        assert_eq!(vec!["target"], globs(&config.exclude_dir));
+
+        let config = parse(&[
+            "-t",
+            "rust",
+            "--type=web",
+            "-Tpython",
+            "--type-add=web:*.html",
+            "to",
+        ])
+        .unwrap();
+        assert_eq!(vec!["*.rs", "*.html"], globs(&config.types));
+        assert_eq!(vec!["*.py", "*.pyi"], globs(&config.types_not));
+        assert!(parse(&["--type-list"]).unwrap().type_list);
+        let error = parse(&["-t", "cobol", "to"]).unwrap_err();
+        assert_eq!(
+            "invalid value 'cobol' for '--type': unknown file type, see --type-list",
+            error.to_string()
+        );
+
+        let error = parse(&["--include", "*.{rs", "to"]).unwrap_err();
+        assert_eq!(
+            "invalid value '*.{rs' for '--include': unclosed brace",
+            error.to_string()
+        );
+        assert!(parse(&["--max-depth", "two", "to", "."]).is_err());
+    }
+
+    #[test]
+    fn multiple_paths() {
+        let config = parse(&["-H", "to", "poem.txt", "src/**/*.rs", "-h"]).unwrap();
+
+        assert_eq!(vec!["poem.txt", "src/**/*.rs"], config.paths);
+        assert_eq!(Some(false), config.with_filename);
+    }
+
+    #[test]
+    fn stdin_by_default() {
+        assert_eq!(vec!["-"], parse(&["to"]).unwrap().paths);
+        assert_eq!(vec!["-"], parse(&["to", "-"]).unwrap().paths);
+    }
+
+    #[test]
+    fn position_flags() {
+        let config = parse(&["-nb", "to", "poem.txt"]).unwrap();
+        assert!(config.line_number && config.byte_offset && !config.column);
+
+        let config = parse(&["--column", "to", "poem.txt"]).unwrap();
+        assert!(config.line_number && config.column);
+    }
+
+    #[test]
+    fn context_flags() {
+        let config = parse(&["-A1", "--context=3", "to", "poem.txt"]).unwrap();
+
+        assert_eq!(1, config.after_context);
+        assert_eq!(3, config.before_context);
+    }
+
+    #[test]
+    fn invert_flag() {
+        assert!(parse(&["-nv", "to", "poem.txt"]).unwrap().invert_match);
+        assert!(parse(&["--invert-match", "to"]).unwrap().invert_match);
+        assert_eq!(OutputMode::Count, parse(&["-vc", "to"]).unwrap().output);
+    }
+
+    #[test]
+    fn limit_flags() {
+        let config = parse(&["-m2", "--max-total", "10", "to"]).unwrap();
+
+        assert_eq!(Some(2), config.max_count);
+        assert_eq!(Some(10), config.max_total);
+        assert_eq!(None, parse(&["to"]).unwrap().max_count);
+        assert!(parse(&["--max-count=-1", "to"]).is_err());
+    }
+
+    #[test]
+    fn parallel_flags() {
+        let config = parse(&["-j4", "--sort=path", "to"]).unwrap();
+        assert_eq!(Some(4), config.jobs);
+        assert_eq!(SortMode::Path, config.sort);
+
+        let config = parse(&["to"]).unwrap();
+        assert_eq!(None, config.jobs);
+        assert_eq!(SortMode::None, config.sort);
+
+        assert!(parse(&["-j", "0", "to"]).is_err());
+        assert!(parse(&["--sort", "size", "to"]).is_err());
+    }
+
+    #[test]
+    fn color_flag() {
+        assert_eq!(ColorChoice::Auto, parse(&["to"]).unwrap().color);
+        assert_eq!(
+            ColorChoice::Never,
+            parse(&["--color=never", "to"]).unwrap().color
+        );
+        assert!(parse(&["--colour", "sometimes", "to"]).is_err());
+    }
+
+    #[test]
+    fn case_flags() {
+        assert!(!parse(&["-i", "to"]).unwrap().case_sensitive);
+        assert!(
+            parse(&["-i", "--case-sensitive", "to"])
+                .unwrap()
+                .case_sensitive
+        );
+        assert!(
+            !parse(&["--case-sensitive", "-S", "to"])
+                .unwrap()
+                .case_sensitive
+        );
+        assert!(parse(&["--smart-case", "To"]).unwrap().case_sensitive);
+    }
+
+    #[test]
+    fn case_precedence() {
+        use CaseMode::*;
+
+        assert_eq!(Sensitive, CaseMode::resolve(None, false));
+        assert_eq!(Insensitive, CaseMode::resolve(None, true));
+        assert_eq!(Sensitive, CaseMode::resolve(Some(Sensitive), true));
+        assert_eq!(Smart, CaseMode::resolve(Some(Smart), true));
+        assert_eq!(Insensitive, CaseMode::resolve(Some(Insensitive), false));
+
+        let patterns = |p: &[&str]| p.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        assert!(Smart.is_case_sensitive(&patterns(&["Émile"]), false));
+        assert!(!Smart.is_case_sensitive(&patterns(&["émile 42"]), false));
+        assert!(Smart.is_case_sensitive(&patterns(&["to", "Be"]), false));
+
+        // Regex escapes are not uppercase letters.
+        let escapes = [
+            r"a\Sb",
+            r"\W\D\B\Ax\z",
+            r"\P{Greek}",
+            r"\pL",
+            r"\x{1F600}",
+            r"\xFF",
+            r"\u00E9",
+        ];
+        for escape in escapes {
+            assert!(!Smart.is_case_sensitive(&patterns(&[escape]), true));
+        }
+        assert!(Smart.is_case_sensitive(&patterns(&[r"a\SB"]), true));
+        assert!(Smart.is_case_sensitive(&patterns(&[r"\p{L}X"]), true));
+        assert!(Smart.is_case_sensitive(&patterns(&[r"a\Sb"]), false));
+        assert!(!parse(&["-S", "-E", r"a\Sb"]).unwrap().case_sensitive);
+    }
+
+    #[test]
+    fn output_modes() {
+        assert_eq!(OutputMode::Lines, parse(&["to"]).unwrap().output);
+        assert_eq!(
+            OutputMode::FilesWithoutMatch,
+            parse(&["-c", "-L", "to"]).unwrap().output
+        );
+
+        let config = parse(&["-lZ", "to"]).unwrap();
+        assert_eq!(OutputMode::FilesWithMatches, config.output);
+        assert!(config.null);
+
+        assert!(!parse(&["to"]).unwrap().json);
+        assert!(parse(&["--json", "to"]).unwrap().json);
+    }
+
+    #[test]
+    fn only_matching_flags() {
+        assert!(parse(&["-o", "to"]).unwrap().only_matching);
+
+        let config = parse(&["-E", "--group=id", "(?P<id>to)"]).unwrap();
+        assert!(config.only_matching);
+        assert_eq!(Some("id".to_string()), config.group);
+    }
+
+    #[test]
+    fn quiet_flags() {
+        let config = parse(&["-qs", "to"]).unwrap();
+        assert!(config.quiet && config.no_messages);
+
+        let config = parse(&["--silent", "to"]).unwrap();
+        assert!(config.quiet && !config.no_messages);
+    }
+
+    #[test]
+    fn argument_errors() {
+        assert_eq!(
+            ArgsError::UnknownFlag("--frobnicate".to_string()),
+            parse(&["--frobnicate", "to", "poem.txt"]).unwrap_err()
+        );
+        assert_eq!(ArgsError::MissingQuery, parse(&[]).unwrap_err());
+    }
+}
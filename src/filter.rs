@@ -0,0 +1,116 @@
+//! Filters on the paths of the files to search, from `--include`,
+//! `--exclude`, `--exclude-dir`, `-t` and `-T`.
+
+use std::path::Path;
+
+use crate::glob::Glob;
+
+/// Globs choosing which files and directories are searched.
+///
+/// A glob with a `/` is matched against the whole path, as it is printed,
+/// without a leading `./`; any other glob is matched against the file name
+/// only, so `*.rs` matches `src/lib.rs`.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    /// When not empty, only files matching one of these are searched.
+    pub include: Vec<Glob>,
+    /// Files matching one of these are skipped, even if included.
+    pub exclude: Vec<Glob>,
+    /// Directories matching one of these are skipped, with all they hold.
+    pub exclude_dir: Vec<Glob>,
+    /// When not empty, only files matching one of these are searched, on top
+    /// of `include`: the globs of the types given with `-t`.
+    pub types: Vec<Glob>,
+    /// Files matching one of these are skipped: the globs of the types given
+    /// with `-T`.
+    pub types_not: Vec<Glob>,
+}
+
+impl PathFilter {
+    /// Returns whether the file at `path` should be searched.
+    pub fn is_file_included(&self, path: &Path) -> bool {
+        (self.include.is_empty() || any_match(&self.include, path))
+            && (self.types.is_empty() || any_match(&self.types, path))
+            && !any_match(&self.exclude, path)
+            && !any_match(&self.types_not, path)
+    }
+
+    /// Returns whether the directory at `path` should be skipped.
+    pub fn is_dir_excluded(&self, path: &Path) -> bool {
+        any_match(&self.exclude_dir, path)
+    }
+}
+
+/// Returns whether any of `globs` matches `path` or its file name.
+fn any_match(globs: &[Glob], path: &Path) -> bool {
+    if globs.is_empty() {
+        return false;
+    }
+
+    let full = path.to_string_lossy().replace('\\', "/");
+    let full = full.strip_prefix("./").unwrap_or(&full);
+    let name = path.file_name().map(|name| name.to_string_lossy());
+
+    globs.iter().any(|glob| {
+        if glob.as_str().contains('/') {
+            glob.is_match(full)
+        } else {
+            name.as_ref().is_some_and(|name| glob.is_match(name))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn globs(patterns: &[&str]) -> Vec<Glob> {
+        patterns.iter().map(|p| Glob::new(p).unwrap()).collect()
+    }
+
+    #[test]
+    fn files() {
+        let filter = PathFilter {
+            include: globs(&["*.{rs,toml}", "docs/**"]),
+            exclude: globs(&["build.rs"]),
+            ..PathFilter::default()
+        };
+
+        assert!(filter.is_file_included(Path::new("./src/lib.rs")));
+        assert!(filter.is_file_included(Path::new("Cargo.toml")));
+        assert!(filter.is_file_included(Path::new("docs/guide/intro.md")));
+        assert!(!filter.is_file_included(Path::new("README.md")));
+        assert!(!filter.is_file_included(Path::new("a/docs/intro.md")));
+        assert!(!filter.is_file_included(Path::new("build.rs")));
+        assert!(PathFilter::default().is_file_included(Path::new("README.md")));
+    }
+
+    #[test]
+    fn types() {
+        let filter = PathFilter {
+            include: globs(&["src/**"]),
+            types: globs(&["*.rs", "*.toml"]),
+            types_not: globs(&["Cargo.toml"]),
+            ..PathFilter::default()
+        };
+
+        assert!(filter.is_file_included(Path::new("src/lib.rs")));
+        assert!(filter.is_file_included(Path::new("src/a/b.toml")));
+        assert!(!filter.is_file_included(Path::new("src/README.md")));
+        assert!(!filter.is_file_included(Path::new("tests/a.rs")));
+        assert!(!filter.is_file_included(Path::new("src/Cargo.toml")));
+    }
+
+    #[test]
+    fn directories() {
+        let filter = PathFilter {
+            exclude_dir: globs(&["target", "vendor/*"]),
+            ..PathFilter::default()
+        };
+
+        assert!(filter.is_dir_excluded(Path::new("a/b/target")));
+        assert!(filter.is_dir_excluded(Path::new("./vendor/regex")));
+        assert!(!filter.is_dir_excluded(Path::new("vendor")));
+        assert!(!filter.is_dir_excluded(Path::new(".")));
+    }
+}
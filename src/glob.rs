@@ -0,0 +1,421 @@
+//! Glob patterns for matching and expanding file paths.
+//!
+//! Patterns are matched against `/`-separated paths:
+//! - `?` matches any single character except `/`
+//! - `*` matches any run of characters except `/`
+//! - `**` as a whole path component matches zero or more components
+//! - `[abc]`, `[a-z]` match one character from a set; `[!a-z]` or `[^a-z]`
+//!   match one character outside it
+//! - `{a,b}` matches any of the comma-separated alternatives, which may hold
+//!   any other syntax, including nested braces
+//! - `\` makes the next character literal
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::filter::PathFilter;
+use crate::walk::{Walk, WalkOptions};
+
+/// An error in the syntax of a glob pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobError {
+    pattern: String,
+    reason: &'static str,
+}
+
+impl fmt::Display for GlobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid glob '{}': {}", self.pattern, self.reason)
+    }
+}
+
+impl GlobError {
+    /// Returns what is wrong with the pattern.
+    pub fn reason(&self) -> &'static str {
+        self.reason
+    }
+}
+
+impl Error for GlobError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(char),
+    Any,
+    Star,
+    /// `**/` at the start or in the middle of a pattern, or a lone `**`.
+    Recursive,
+    /// A trailing `/**`, which matches the directory itself and everything below it.
+    RecursiveSuffix,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+/// A compiled glob pattern.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pattern: String,
+    /// The tokens of each pattern that braces expand to.
+    alternatives: Vec<Vec<Token>>,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Glob, GlobError> {
+        let error = |reason| GlobError {
+            pattern: pattern.to_string(),
+            reason,
+        };
+
+        let chars: Vec<char> = pattern.chars().collect();
+        let alternatives = expand_braces(&chars)
+            .map_err(error)?
+            .iter()
+            .map(|chars| parse_tokens(chars).map_err(error))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Glob {
+            pattern: pattern.to_string(),
+            alternatives,
+        })
+    }
+
+    /// Returns whether a string contains any glob syntax.
+    pub fn is_glob(s: &str) -> bool {
+        s.contains(['*', '?', '[', '{'])
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Returns whether the whole of `path` matches the pattern.
+    pub fn is_match(&self, path: &str) -> bool {
+        let path: Vec<char> = path.chars().collect();
+        self.alternatives
+            .iter()
+            .any(|tokens| match_tokens(tokens, &path))
+    }
+}
+
+/// Expands the first braces of a pattern, and those of each resulting
+/// pattern in turn, into patterns without braces.
+///
+/// Escaped characters and character classes are kept as they are, for
+/// `parse_tokens`; a `{` or `,` in them is literal.
+fn expand_braces(chars: &[char]) -> Result<Vec<Vec<char>>, &'static str> {
+    let mut open = None;
+    // The positions of the commas between alternatives, then of the closing brace.
+    let mut separators = Vec::new();
+    let mut depth = 0;
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '[' => {
+                if let Some((_, next)) = parse_class(chars, i + 1) {
+                    i = next;
+                    continue;
+                }
+            }
+            '{' => {
+                if depth == 0 {
+                    open = Some(i);
+                }
+                depth += 1;
+            }
+            ',' if depth == 1 => separators.push(i),
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    separators.push(i);
+                    break;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let open = match open {
+        Some(open) if depth == 0 => open,
+        Some(_) => return Err("unclosed brace"),
+        None => return Ok(vec![chars.to_vec()]),
+    };
+    let close = separators[separators.len() - 1];
+
+    let mut patterns = Vec::new();
+    let mut start = open + 1;
+    for &end in &separators {
+        let mut pattern = chars[..open].to_vec();
+        pattern.extend_from_slice(&chars[start..end]);
+        pattern.extend_from_slice(&chars[close + 1..]);
+        patterns.extend(expand_braces(&pattern)?);
+        start = end + 1;
+    }
+    Ok(patterns)
+}
+
+/// Parses a pattern without braces.
+fn parse_tokens(chars: &[char]) -> Result<Vec<Token>, &'static str> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '?' => tokens.push(Token::Any),
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let starts_component = i == 0 || chars[i - 1] == '/';
+                let ends_component = i + 2 == chars.len() || chars[i + 2] == '/';
+                if !starts_component || !ends_component {
+                    return Err("`**` must be a whole path component");
+                }
+
+                if i + 2 == chars.len() {
+                    if tokens.last() == Some(&Token::Literal('/')) {
+                        tokens.pop();
+                        tokens.push(Token::RecursiveSuffix);
+                    } else {
+                        tokens.push(Token::Recursive);
+                    }
+                    i += 2;
+                } else {
+                    tokens.push(Token::Recursive);
+                    i += 3;
+                }
+                continue;
+            }
+            '*' => tokens.push(Token::Star),
+            '[' => {
+                let (token, next) = parse_class(chars, i + 1).ok_or("unclosed character class")?;
+                tokens.push(token);
+                i = next;
+                continue;
+            }
+            '\\' => {
+                i += 1;
+                let c = chars.get(i).ok_or("dangling escape")?;
+                tokens.push(Token::Literal(*c));
+            }
+            c => tokens.push(Token::Literal(c)),
+        }
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+/// Parses a character class starting just after its `[`.
+///
+/// Returns the class and the index just after its closing `]`.
+fn parse_class(chars: &[char], mut i: usize) -> Option<(Token, usize)> {
+    let negated = matches!(chars.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut ranges = Vec::new();
+    let start = i;
+    loop {
+        let c = *chars.get(i)?;
+        // A `]` right after the opening bracket is a literal member.
+        if c == ']' && i > start {
+            return Some((Token::Class { negated, ranges }, i + 1));
+        }
+
+        if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|&end| end != ']') {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+}
+
+fn match_tokens(tokens: &[Token], path: &[char]) -> bool {
+    let token = match tokens.first() {
+        Some(token) => token,
+        None => return path.is_empty(),
+    };
+    let rest = &tokens[1..];
+
+    match token {
+        Token::Literal(c) => path.first() == Some(c) && match_tokens(rest, &path[1..]),
+        Token::Any => path.first().is_some_and(|&c| c != '/') && match_tokens(rest, &path[1..]),
+        Token::Class { negated, ranges } => {
+            let c = match path.first() {
+                Some(&c) if c != '/' => c,
+                _ => return false,
+            };
+            let in_class = ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+            in_class != *negated && match_tokens(rest, &path[1..])
+        }
+        Token::Star => {
+            for i in 0..=path.len() {
+                if match_tokens(rest, &path[i..]) {
+                    return true;
+                }
+                if path.get(i) == Some(&'/') {
+                    return false;
+                }
+            }
+            false
+        }
+        Token::Recursive => {
+            // Zero components, or skip whole components one at a time.
+            if rest.is_empty() || match_tokens(rest, path) {
+                return true;
+            }
+            path.iter()
+                .enumerate()
+                .filter(|&(_, &c)| c == '/')
+                .any(|(i, _)| match_tokens(rest, &path[i + 1..]))
+        }
+        Token::RecursiveSuffix => path.first().is_none_or(|&c| c == '/'),
+    }
+}
+
+/// Expands a glob pattern into the files it matches, in walk order.
+///
+/// The walk starts from the longest leading run of path components that have
+/// no glob syntax. Hidden entries are only considered when the pattern itself
+/// mentions a component starting with `.`.
+pub fn expand(pattern: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let glob = Glob::new(pattern)?;
+
+    let components: Vec<&str> = pattern.split('/').collect();
+    let literal = components
+        .iter()
+        .take_while(|component| !Glob::is_glob(component))
+        .count()
+        .min(components.len() - 1);
+    let base = literal_base(&components[..literal]);
+    let glob_part = &components[literal..];
+
+    let options = WalkOptions {
+        max_depth: if glob_part.contains(&"**") {
+            None
+        } else {
+            Some(glob_part.len())
+        },
+        follow_links: false,
+        skip_hidden: !components
+            .iter()
+            .any(|component| component.starts_with('.') && *component != "." && *component != ".."),
+        ignore: false,
+        global_excludes: None,
+        filter: PathFilter::default(),
+    };
+
+    let root = if base.is_empty() { "." } else { base.as_str() };
+    let mut paths = Vec::new();
+    for path in Walk::new(Path::new(root), options) {
+        let path = path?;
+        let display = path.to_string_lossy().replace('\\', "/");
+        let display = if base.is_empty() {
+            display.strip_prefix("./").unwrap_or(&display).to_string()
+        } else {
+            display
+        };
+
+        if glob.is_match(&display) {
+            paths.push(PathBuf::from(display));
+        }
+    }
+
+    if paths.is_empty() {
+        let message = format!("{}: no files match this pattern", pattern);
+        return Err(io::Error::new(io::ErrorKind::NotFound, message).into());
+    }
+
+    Ok(paths)
+}
+
+/// Joins the leading components of a pattern that have no glob syntax into
+/// the directory to walk from. For an absolute pattern, the first component
+/// is empty, and the root is at least `/`.
+fn literal_base(components: &[&str]) -> String {
+    match components {
+        [""] => "/".to_string(),
+        _ => components.join("/"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern).unwrap().is_match(path)
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(is_match("*.rs", "lib.rs"));
+        assert!(!is_match("*.rs", "src/lib.rs"));
+        assert!(is_match("src/?ib.rs", "src/lib.rs"));
+        assert!(!is_match("src/?ib.rs", "src/ib.rs"));
+        assert!(is_match("src/*", "src/main.rs"));
+    }
+
+    #[test]
+    fn recursive_wildcards() {
+        assert!(is_match("src/**/*.rs", "src/lib.rs"));
+        assert!(is_match("src/**/*.rs", "src/a/b/c.rs"));
+        assert!(!is_match("src/**/*.rs", "tests/a.rs"));
+        assert!(is_match("**/target", "target"));
+        assert!(is_match("**/target", "a/b/target"));
+        assert!(is_match("target/**", "target/debug/x"));
+        assert!(is_match("target/**", "target"));
+        assert!(!is_match("target/**", "targets"));
+        assert!(is_match("**", "a/b"));
+        assert!(Glob::new("src/a**").is_err());
+    }
+
+    #[test]
+    fn character_classes() {
+        assert!(is_match("[abc].txt", "b.txt"));
+        assert!(is_match("file[0-9]", "file7"));
+        assert!(!is_match("file[!0-9]", "file7"));
+        assert!(is_match("file[^0-9]", "fileX"));
+        assert!(is_match("[]]", "]"));
+        assert!(is_match("a[-]b", "a-b"));
+        assert!(Glob::new("[abc").is_err());
+    }
+
+    #[test]
+    fn absolute_patterns() {
+        assert_eq!("/", literal_base(&[""]));
+        assert_eq!("/tmp", literal_base(&["", "tmp"]));
+        assert_eq!("", literal_base(&[]));
+        assert_eq!("src", literal_base(&["src"]));
+
+        let dir = std::env::temp_dir().join("minigrep-glob-absolute");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.json"), "").unwrap();
+        std::fs::write(dir.join("b.txt"), "").unwrap();
+        let pattern = format!("{}/*.json", dir.display());
+        assert_eq!(vec![dir.join("a.json")], expand(&pattern).unwrap());
+    }
+
+    #[test]
+    fn braces() {
+        assert!(is_match("*.{rs,toml}", "lib.rs"));
+        assert!(is_match("*.{rs,toml}", "Cargo.toml"));
+        assert!(!is_match("*.{rs,toml}", "README.md"));
+        assert!(is_match("{src,tests}/**/*.rs", "tests/a/b.rs"));
+        assert!(is_match("a{b,c{d,e}}f", "acef"));
+        assert!(is_match("a{,.bak}", "a"));
+        assert!(is_match("[{]x}", "{x}"));
+        assert!(is_match("\\{a,b}", "{a,b}"));
+        assert!(is_match("{**/,}x", "a/x"));
+        assert!(Glob::new("{a,b").is_err());
+        assert!(Glob::is_glob("*.{rs,toml}"));
+    }
+}
@@ -0,0 +1,279 @@
+//! Ignore files, which list paths to skip while walking directories.
+//!
+//! Each line of an ignore file is a glob pattern, following the rules of
+//! `.gitignore`:
+//! - blank lines and lines starting with `#` are skipped
+//! - a leading `!` includes again a path excluded by an earlier pattern
+//! - a trailing `/` only matches directories
+//! - a pattern with a `/` at the start or in the middle is relative to the
+//!   directory of the ignore file; otherwise it matches a name at any depth
+//! - `\` escapes a leading `#` or `!`, and trailing spaces, which are
+//!   otherwise dropped
+//!
+//! Within a file, the last pattern matching a path decides whether it is
+//! ignored. Patterns that are not valid globs are skipped.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::glob::Glob;
+
+/// The patterns of one ignore file.
+#[derive(Debug, Clone)]
+pub struct IgnoreFile {
+    /// The directory the patterns are relative to.
+    dir: PathBuf,
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    glob: Glob,
+    /// Whether the pattern starts with `!`, including the paths it matches.
+    negated: bool,
+    /// Whether the pattern ends with `/`, matching only directories.
+    dir_only: bool,
+}
+
+impl IgnoreFile {
+    /// Parses the `contents` of an ignore file whose patterns are relative to `dir`.
+    pub fn parse(dir: &Path, contents: &str) -> IgnoreFile {
+        IgnoreFile {
+            dir: dir.to_path_buf(),
+            rules: contents.lines().filter_map(parse_rule).collect(),
+        }
+    }
+
+    /// Reads the ignore file at `path`, if it exists and can be read.
+    pub fn open(dir: &Path, path: &Path) -> Option<IgnoreFile> {
+        let contents = fs::read_to_string(path).ok()?;
+        Some(IgnoreFile::parse(dir, &contents))
+    }
+
+    /// Returns whether `path` is ignored (`Some(true)`), included again by a
+    /// negated pattern (`Some(false)`) or not matched at all (`None`).
+    pub fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.dir).ok()?;
+        let relative = relative.to_string_lossy().replace('\\', "/");
+
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.glob.is_match(&relative))
+            .map(|rule| !rule.negated)
+    }
+}
+
+/// Parses one line of an ignore file, returning `None` for blank lines,
+/// comments and invalid patterns.
+fn parse_rule(line: &str) -> Option<Rule> {
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    if line.starts_with('#') {
+        return None;
+    }
+
+    let mut end = line.len();
+    while line[..end].ends_with(' ') && !line[..end - 1].ends_with('\\') {
+        end -= 1;
+    }
+    let mut pattern = &line[..end];
+
+    let negated = pattern.starts_with('!');
+    if negated {
+        pattern = &pattern[1..];
+    }
+    let dir_only = pattern.ends_with('/');
+    if dir_only {
+        pattern = &pattern[..pattern.len() - 1];
+    }
+    if pattern.is_empty() {
+        return None;
+    }
+
+    let glob = if pattern.contains('/') {
+        Glob::new(pattern.strip_prefix('/').unwrap_or(pattern))
+    } else {
+        Glob::new(&format!("**/{}", pattern))
+    };
+    Some(Rule {
+        glob: glob.ok()?,
+        negated,
+        dir_only,
+    })
+}
+
+/// The ignore files that apply to the entries of a directory: its own, and
+/// those of its parents.
+///
+/// `.ignore` files win over `.gitignore` files, which win over
+/// `.git/info/exclude` and the global git excludes file. Among files of the
+/// same kind, the one in the deepest directory wins.
+#[derive(Debug, Default)]
+pub struct Ignore {
+    parent: Option<Arc<Ignore>>,
+    ignore: Option<IgnoreFile>,
+    gitignore: Option<IgnoreFile>,
+    /// `.git/info/exclude` then the global excludes, when the directory holds
+    /// a git repository.
+    excludes: Vec<IgnoreFile>,
+}
+
+impl Ignore {
+    /// Reads the ignore files of `dir`, on top of those of its `parent`
+    /// directory. `global_excludes` holds the contents of the global git
+    /// excludes file, which applies to every repository.
+    pub fn new(parent: Option<Arc<Ignore>>, dir: &Path, global_excludes: Option<&str>) -> Ignore {
+        let mut excludes = Vec::new();
+        let git = dir.join(".git");
+        if git.exists() {
+            excludes.extend(IgnoreFile::open(dir, &git.join("info/exclude")));
+            excludes.extend(global_excludes.map(|contents| IgnoreFile::parse(dir, contents)));
+        }
+
+        Ignore {
+            parent,
+            ignore: IgnoreFile::open(dir, &dir.join(".ignore")),
+            gitignore: IgnoreFile::open(dir, &dir.join(".gitignore")),
+            excludes,
+        }
+    }
+
+    /// Returns whether `path`, below the directory of these files, is ignored.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let kinds: [fn(&Ignore) -> &[IgnoreFile]; 3] = [
+            |ignore| ignore.ignore.as_slice(),
+            |ignore| ignore.gitignore.as_slice(),
+            |ignore| &ignore.excludes,
+        ];
+
+        for files in kinds {
+            let mut level = Some(self);
+            while let Some(ignore) = level {
+                for file in files(ignore) {
+                    if let Some(ignored) = file.matched(path, is_dir) {
+                        return ignored;
+                    }
+                }
+                level = ignore.parent.as_deref();
+            }
+        }
+        false
+    }
+}
+
+/// Reads the global git excludes file: the `core.excludesFile` of the user's
+/// git config, or `$XDG_CONFIG_HOME/git/ignore` by default.
+pub fn global_excludes() -> Option<String> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home.as_ref().map(|home| home.join(".config")))?;
+
+    // Git reads `~/.gitconfig` after the XDG one, so it wins.
+    let configs = [
+        home.as_ref().map(|home| home.join(".gitconfig")),
+        Some(config_home.join("git/config")),
+    ];
+    let configured = configs
+        .iter()
+        .flatten()
+        .filter_map(|config| fs::read_to_string(config).ok())
+        .find_map(|contents| excludes_file(&contents));
+
+    let path = match configured {
+        Some(path) => match (path.strip_prefix("~/"), &home) {
+            (Some(rest), Some(home)) => home.join(rest),
+            _ => PathBuf::from(path),
+        },
+        None => config_home.join("git/ignore"),
+    };
+    fs::read_to_string(path).ok()
+}
+
+/// Finds the `excludesFile` setting of the `[core]` section in the contents
+/// of a git config file.
+fn excludes_file(config: &str) -> Option<String> {
+    let mut in_core = false;
+    let mut found = None;
+
+    for line in config.lines().map(str::trim) {
+        if let Some(section) = line.strip_prefix('[') {
+            let name = section.split(']').next().unwrap_or("").trim();
+            in_core = name.eq_ignore_ascii_case("core");
+            continue;
+        }
+
+        if let Some((key, value)) = line.split_once('=') {
+            if in_core && key.trim().eq_ignore_ascii_case("excludesfile") {
+                found = Some(value.trim().trim_matches('"').to_string());
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matched(contents: &str, path: &str, is_dir: bool) -> Option<bool> {
+        let file = IgnoreFile::parse(Path::new("/repo"), contents);
+        file.matched(&Path::new("/repo").join(path), is_dir)
+    }
+
+    #[test]
+    fn patterns() {
+        assert_eq!(Some(true), matched("*.log", "a/b/debug.log", false));
+        assert_eq!(None, matched("*.log", "debug.txt", false));
+        assert_eq!(Some(true), matched("target", "a/target", true));
+        assert_eq!(Some(true), matched("/target", "target", true));
+        assert_eq!(None, matched("/target", "a/target", true));
+        assert_eq!(Some(true), matched("doc/*.html", "doc/index.html", false));
+        assert_eq!(None, matched("doc/*.html", "a/doc/index.html", false));
+        assert_eq!(
+            Some(true),
+            matched("**/doc/*.html", "a/doc/index.html", false)
+        );
+        assert_eq!(None, matched("*.log", "/elsewhere/debug.log", false));
+    }
+
+    #[test]
+    fn directories_only() {
+        assert_eq!(Some(true), matched("build/", "build", true));
+        assert_eq!(None, matched("build/", "build", false));
+        assert_eq!(Some(true), matched("src/build/", "src/build", true));
+    }
+
+    #[test]
+    fn negation_and_order() {
+        let contents = "*.log\n!keep.log\n";
+        assert_eq!(Some(true), matched(contents, "debug.log", false));
+        assert_eq!(Some(false), matched(contents, "keep.log", false));
+        assert_eq!(Some(true), matched("!keep.log\n*.log\n", "keep.log", false));
+    }
+
+    #[test]
+    fn comments_and_escapes() {
+        let contents = "# comment\n\n\\#notes\n\\!bang\ntrailing  \nspace\\ \n";
+        assert_eq!(None, matched(contents, "# comment", false));
+        assert_eq!(Some(true), matched(contents, "#notes", false));
+        assert_eq!(Some(true), matched(contents, "!bang", false));
+        assert_eq!(Some(true), matched(contents, "trailing", false));
+        assert_eq!(Some(true), matched(contents, "space ", false));
+        assert_eq!(None, matched("[abc\nfoo**\n", "foo", false));
+    }
+
+    #[test]
+    fn git_config() {
+        let config = "[user]\n\texcludesFile = no\n[core]\n\tautocrlf = false\n\
+                      \texcludesfile = \"~/.gitignore_global\"\n[alias]\n";
+        assert_eq!(
+            Some("~/.gitignore_global".to_string()),
+            excludes_file(config)
+        );
+        assert_eq!(None, excludes_file("[core]\n\tbare = false\n"));
+    }
+}
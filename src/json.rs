@@ -0,0 +1,311 @@
+//! Writes search results as JSON Lines, for `--json`.
+//!
+//! Every event is a JSON object on its own line, with a `type` and some
+//! `data`:
+//!
+//! - `begin`, before searching an input: `path`
+//! - `match`, for each matching line: `path`, `line`, `line_number`,
+//!   `absolute_offset` (of the start of the line, in bytes) and `submatches`,
+//!   each with the matched `match` and its `start` and `end` in bytes within
+//!   the line
+//! - `context`, for each context line, with the same fields as `match` and no
+//!   submatches
+//! - `end`, after searching an input: `path` and `stats`
+//! - `summary`, once all inputs are searched: `stats`
+//!
+//! Paths, lines and matches are objects holding either `text`, when they are
+//! valid UTF-8, or `bytes`, the raw bytes in base64. Lines do not include
+//! their line terminator. Paths are only written as they are on Unix;
+//! elsewhere, those that are not valid Unicode are converted lossily.
+
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::str;
+
+use crate::matcher::Matcher;
+use crate::searcher::{ContextLine, LineMatch, Sink};
+
+/// A `Sink` that writes each matching and context line as a JSON event.
+pub struct JsonPrinter<W> {
+    out: W,
+    matcher: Matcher,
+    /// The path of the input being searched.
+    path: Vec<u8>,
+    searches: u64,
+    searches_with_match: u64,
+    matched_lines: u64,
+}
+
+impl<W: Write> JsonPrinter<W> {
+    pub fn new(out: W, matcher: &Matcher) -> JsonPrinter<W> {
+        JsonPrinter {
+            out,
+            matcher: matcher.clone(),
+            path: Vec::new(),
+            searches: 0,
+            searches_with_match: 0,
+            matched_lines: 0,
+        }
+    }
+
+    /// Writes the `begin` event of an input.
+    pub fn begin(&mut self, path: &Path) -> io::Result<()> {
+        self.path = path_bytes(path);
+        self.searches += 1;
+
+        let mut event = Vec::new();
+        event.extend_from_slice(br#"{"type":"begin","data":{"path":"#);
+        write_data(&mut event, &self.path);
+        event.extend_from_slice(b"}}\n");
+        self.out.write_all(&event)
+    }
+
+    /// Writes the `end` event of the current input, in which `matched_lines`
+    /// lines matched.
+    pub fn end(&mut self, matched_lines: u64) -> io::Result<()> {
+        self.matched_lines += matched_lines;
+        if matched_lines > 0 {
+            self.searches_with_match += 1;
+        }
+
+        let mut event = Vec::new();
+        event.extend_from_slice(br#"{"type":"end","data":{"path":"#);
+        write_data(&mut event, &self.path);
+        write!(
+            event,
+            r#","stats":{{"matched_lines":{}}}}}}}"#,
+            matched_lines
+        )?;
+        event.push(b'\n');
+        self.out.write_all(&event)
+    }
+
+    /// Writes the `summary` event, with totals across all inputs.
+    pub fn summary(&mut self) -> io::Result<()> {
+        writeln!(
+            self.out,
+            r#"{{"type":"summary","data":{{"stats":{{"searches":{},"searches_with_match":{},"matched_lines":{}}}}}}}"#,
+            self.searches, self.searches_with_match, self.matched_lines
+        )?;
+        self.out.flush()
+    }
+
+    /// Writes the events of a whole input, as written by another printer,
+    /// counting its `matched_lines` in the summary.
+    pub fn write_input(&mut self, events: &[u8], matched_lines: u64) -> io::Result<()> {
+        self.searches += 1;
+        self.matched_lines += matched_lines;
+        if matched_lines > 0 {
+            self.searches_with_match += 1;
+        }
+        self.out.write_all(events)
+    }
+
+    /// Writes a `match` or `context` event.
+    fn write_line(
+        &mut self,
+        kind: &str,
+        line: &[u8],
+        line_number: u64,
+        byte_offset: u64,
+        submatches: &[Range<usize>],
+    ) -> io::Result<()> {
+        let mut event = Vec::new();
+        write!(event, r#"{{"type":"{}","data":{{"path":"#, kind)?;
+        write_data(&mut event, &self.path);
+        event.extend_from_slice(br#","line":"#);
+        write_data(&mut event, line);
+        write!(
+            event,
+            r#","line_number":{},"absolute_offset":{},"submatches":["#,
+            line_number, byte_offset
+        )?;
+        for (i, span) in submatches.iter().enumerate() {
+            if i > 0 {
+                event.push(b',');
+            }
+            event.extend_from_slice(br#"{"match":"#);
+            write_data(&mut event, &line[span.clone()]);
+            write!(event, r#","start":{},"end":{}}}"#, span.start, span.end)?;
+        }
+        event.extend_from_slice(b"]}}\n");
+        self.out.write_all(&event)
+    }
+}
+
+impl JsonPrinter<Vec<u8>> {
+    /// Takes the events written so far.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.out)
+    }
+}
+
+impl<W: Write> Sink for JsonPrinter<W> {
+    fn matched(&mut self, m: &LineMatch) -> io::Result<bool> {
+        // An empty span is an inverted match, which has no submatches.
+        let submatches: Vec<_> = if m.span.is_empty() {
+            Vec::new()
+        } else {
+            self.matcher.find_iter(m.line).collect()
+        };
+        self.write_line("match", m.line, m.line_number, m.byte_offset, &submatches)?;
+        Ok(true)
+    }
+
+    fn context(&mut self, c: &ContextLine) -> io::Result<bool> {
+        self.write_line("context", c.line, c.line_number, c.byte_offset, &[])?;
+        Ok(true)
+    }
+}
+
+/// Returns the raw bytes of `path`, which need not be valid UTF-8 on Unix.
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+/// Writes `bytes` as `{"text":...}` when they are valid UTF-8, or as
+/// `{"bytes":...}` in base64 otherwise.
+fn write_data(out: &mut Vec<u8>, bytes: &[u8]) {
+    match str::from_utf8(bytes) {
+        Ok(text) => {
+            out.extend_from_slice(br#"{"text":"#);
+            write_string(out, text);
+        }
+        Err(_) => {
+            out.extend_from_slice(br#"{"bytes":""#);
+            write_base64(out, bytes);
+            out.push(b'"');
+        }
+    }
+    out.push(b'}');
+}
+
+/// Writes `s` as a JSON string, with its quotes.
+fn write_string(out: &mut Vec<u8>, s: &str) {
+    out.push(b'"');
+    for c in s.chars() {
+        match c {
+            '"' => out.extend_from_slice(br#"\""#),
+            '\\' => out.extend_from_slice(br"\\"),
+            '\n' => out.extend_from_slice(br"\n"),
+            '\r' => out.extend_from_slice(br"\r"),
+            '\t' => out.extend_from_slice(br"\t"),
+            c if c < ' ' => {
+                out.extend_from_slice(format!(r"\u{:04x}", c as u32).as_bytes());
+            }
+            c => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    out.push(b'"');
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Writes `bytes` in standard base64, with padding.
+fn write_base64(out: &mut Vec<u8>, bytes: &[u8]) {
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize]);
+            } else {
+                out.push(b'=');
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::searcher::Searcher;
+    use crate::Config;
+
+    fn encode(f: fn(&mut Vec<u8>, &[u8]), bytes: &[u8]) -> String {
+        let mut out = Vec::new();
+        f(&mut out, bytes);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn strings_and_bytes() {
+        assert_eq!(
+            r#"{"text":"say \"hi\"\\\t\u0001 é"}"#,
+            encode(write_data, "say \"hi\"\\\t\u{1} é".as_bytes())
+        );
+        assert_eq!(r#"{"bytes":"/2E="}"#, encode(write_data, b"\xffa"));
+
+        assert_eq!("", encode(write_base64, b""));
+        assert_eq!("Zg==", encode(write_base64, b"f"));
+        assert_eq!("Zm8=", encode(write_base64, b"fo"));
+        assert_eq!("Zm9v", encode(write_base64, b"foo"));
+        assert_eq!("Zm9vYmFy", encode(write_base64, b"foobar"));
+    }
+
+    #[test]
+    fn events() {
+        let config = Config {
+            patterns: vec!["o".to_string()],
+            case_sensitive: true,
+            before_context: 1,
+            ..Config::default()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+        let mut out = Vec::new();
+        let mut printer = JsonPrinter::new(&mut out, &matcher);
+
+        printer.begin(Path::new("poem.txt")).unwrap();
+        let matches = Searcher::new(&config)
+            .search_reader(&matcher, &b"a\r\nfoo\nb\n\xffo"[..], &mut printer)
+            .unwrap();
+        printer.end(matches).unwrap();
+        printer.begin(Path::new("empty")).unwrap();
+        printer.end(0).unwrap();
+        printer.summary().unwrap();
+
+        let expected = [
+            r#"{"type":"begin","data":{"path":{"text":"poem.txt"}}}"#,
+            r#"{"type":"context","data":{"path":{"text":"poem.txt"},"line":{"text":"a"},"line_number":1,"absolute_offset":0,"submatches":[]}}"#,
+            r#"{"type":"match","data":{"path":{"text":"poem.txt"},"line":{"text":"foo"},"line_number":2,"absolute_offset":3,"submatches":[{"match":{"text":"o"},"start":1,"end":2},{"match":{"text":"o"},"start":2,"end":3}]}}"#,
+            r#"{"type":"context","data":{"path":{"text":"poem.txt"},"line":{"text":"b"},"line_number":3,"absolute_offset":7,"submatches":[]}}"#,
+            r#"{"type":"match","data":{"path":{"text":"poem.txt"},"line":{"bytes":"/28="},"line_number":4,"absolute_offset":9,"submatches":[{"match":{"text":"o"},"start":1,"end":2}]}}"#,
+            r#"{"type":"end","data":{"path":{"text":"poem.txt"},"stats":{"matched_lines":2}}}"#,
+            r#"{"type":"begin","data":{"path":{"text":"empty"}}}"#,
+            r#"{"type":"end","data":{"path":{"text":"empty"},"stats":{"matched_lines":0}}}"#,
+            r#"{"type":"summary","data":{"stats":{"searches":2,"searches_with_match":1,"matched_lines":2}}}"#,
+        ];
+        assert_eq!(expected.join("\n") + "\n", String::from_utf8(out).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn paths_that_are_not_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let matcher = Matcher::new(&Config::default()).unwrap();
+        let mut out = Vec::new();
+        let mut printer = JsonPrinter::new(&mut out, &matcher);
+        printer
+            .begin(Path::new(OsStr::from_bytes(b"caf\xe9.txt")))
+            .unwrap();
+
+        assert_eq!(
+            "{\"type\":\"begin\",\"data\":{\"path\":{\"bytes\":\"Y2Fm6S50eHQ=\"}}}\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
@@ -2,61 +2,540 @@
 //!
 //! `minigrep` is a light version of the popular command-line utility `grep`
 
+use std::collections::BTreeMap;
 use std::error::Error;
-use std::{env, fs};
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::iter;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 
-#[derive(Debug)]
-pub struct Config {
-    pub query: String,
-    pub filename: String,
-    pub case_sensitive: bool,
+use regex::Regex;
+
+pub mod args;
+pub mod casefold;
+pub mod color;
+mod config;
+pub mod filter;
+pub mod glob;
+pub mod ignore;
+mod json;
+pub mod matcher;
+mod printer;
+pub mod searcher;
+pub mod types;
+pub mod walk;
+
+pub use args::ArgsError;
+use casefold::CaseFolder;
+use color::Colors;
+pub use config::{Config, OutputMode, SortMode};
+use filter::PathFilter;
+use glob::Glob;
+use json::JsonPrinter;
+pub use matcher::Matcher;
+use printer::Printer;
+pub use searcher::{search_reader, ContextLine, LineMatch, Searcher, Sink};
+use walk::{Walk, WalkOptions};
+
+/// Runs the program using the config and search functions.
+///
+/// Performs the following operations:
+/// - expands glob patterns and walks directories given as paths, skipping
+///   hidden files and those listed in ignore files unless asked not to, and
+///   those left out by `--include`, `--exclude`, `--exclude-dir`, `-t` and `-T`
+/// - streams every file found, or standard input when the path is `-`, line
+///   by line through the matcher built from the config
+/// - prints each matching line as soon as it is found, or only how many lines
+///   match in each file, or only the names of the files that (don't) match,
+///   with any context lines
+///   around it, prefixed with the file path when more than one file may be
+///   searched and with its position when asked to, highlighting matches in
+///   color when enabled
+/// - stops reading each input after `--max-count` matching lines, and stops
+///   altogether after `--max-total` matching lines across all inputs
+/// - searches several files at once on `-j` threads, one per CPU by default,
+///   printing the results of each file together; they come in the order the
+///   files are found with `--sort path`, or as they are done otherwise
+/// - with `--json`, prints every event of the search as JSON Lines instead
+/// - with `-q`, prints nothing and stops at the first match
+/// - returns the `Outcome` of the search if successful
+///
+/// Files that cannot be read are reported on stderr, unless `-s` is given, and
+/// skipped.
+pub fn run(config: Config) -> Result<Outcome, Box<dyn Error>> {
+    let matcher = Matcher::new(&config)?;
+    let mut outcome = Outcome {
+        quiet: config.quiet,
+        ..Outcome::default()
+    };
+
+    let mut inputs = Vec::new();
+    for path in &config.paths {
+        if Glob::is_glob(path) && !Path::new(path).exists() {
+            match glob::expand(path) {
+                Ok(paths) => inputs.extend(paths),
+                Err(e) => outcome.error(&config, e),
+            }
+        } else {
+            inputs.push(PathBuf::from(path));
+        }
+    }
+
+    let many_files = inputs.len() > 1 || inputs.iter().any(|path| path.is_dir());
+    let with_filename = config.with_filename.unwrap_or(many_files);
+    let options = WalkOptions {
+        max_depth: config.max_depth,
+        follow_links: config.follow,
+        skip_hidden: config.skip_hidden,
+        ignore: !config.no_ignore,
+        global_excludes: if config.no_ignore {
+            None
+        } else {
+            ignore::global_excludes().map(Arc::from)
+        },
+        filter: PathFilter {
+            include: config.include.clone(),
+            exclude: config.exclude.clone(),
+            exclude_dir: config.exclude_dir.clone(),
+            types: config.types.clone(),
+            types_not: config.types_not.clone(),
+        },
+    };
+    let stdout = io::stdout();
+    let colors = if config.color.enabled(stdout.is_terminal()) {
+        Some(Colors::from_env())
+    } else {
+        None
+    };
+    let mut output = Output::new(stdout.lock(), &config, &matcher, colors.clone());
+    let mut search = InputSearch {
+        config: &config,
+        matcher: &matcher,
+        searcher: Searcher::new(&config),
+        with_filename,
+    };
+
+    let jobs = config
+        .jobs
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZeroUsize::get));
+    let paths = walk_inputs(&inputs, &options);
+    // `--max-total` needs to know how much each file matched before the next
+    // one is searched, and a single file is better streamed as it is read.
+    let result = if jobs > 1 && many_files && config.max_total.is_none() {
+        search_parallel(&search, paths, jobs, colors, &mut output, &mut outcome)
+    } else {
+        search_serial(&mut search, paths, &mut output, &mut outcome)
+    };
+
+    let result = result.and_then(|()| match &mut output {
+        Output::Json(json) if !config.quiet => json.summary(),
+        _ => Ok(()),
+    });
+    match result {
+        // The reader went away, as with `minigrep ... | head`.
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(outcome),
+        Err(e) => Err(e.into()),
+        Ok(()) => Ok(outcome),
+    }
 }
 
-impl Config {
-    pub fn new(mut args: env::Args) -> Result<Config, &'static str> {
-        args.next();
+/// Lists the files to search: the paths given, with every file found under
+/// the directories among them. `-` stands for standard input.
+fn walk_inputs<'a>(
+    inputs: &'a [PathBuf],
+    options: &'a WalkOptions,
+) -> impl Iterator<Item = io::Result<PathBuf>> + Send + 'a {
+    inputs.iter().flat_map(
+        move |input| -> Box<dyn Iterator<Item = io::Result<PathBuf>> + Send> {
+            if input.as_os_str() == "-" {
+                Box::new(iter::once(Ok(input.clone())))
+            } else {
+                Box::new(Walk::new(input, options.clone()))
+            }
+        },
+    )
+}
 
-        let query = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a query string"),
-        };
+/// Searches the files one at a time, printing results as soon as they are found.
+fn search_serial<W, I>(
+    search: &mut InputSearch,
+    paths: I,
+    output: &mut Output<W>,
+    outcome: &mut Outcome,
+) -> io::Result<()>
+where
+    W: Write,
+    I: Iterator<Item = io::Result<PathBuf>>,
+{
+    let config = search.config;
+    // How many more matching lines may be printed, with `--max-total`.
+    let mut remaining = config.max_total;
+
+    for path in paths {
+        search.searcher.max_count = min_limit(config.max_count, remaining);
+        match search.search_path(path, output) {
+            Err(InputError::Search(_, e)) if e.error.kind() == io::ErrorKind::BrokenPipe => {
+                // The lines printed before the reader went away may have matched.
+                outcome.matched |= e.matches > 0;
+                return Err(e.error);
+            }
+            Err(e) => outcome.error(config, e),
+            Ok(matches) => {
+                outcome.matched |= matches > 0;
+                if take(&mut remaining, matches) || (config.quiet && outcome.matched) {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Searches the files on `jobs` threads, printing the results of each file
+/// whole, once it is done, so that the lines of different files never
+/// interleave. With `--sort path`, the results are printed in the order of
+/// `paths`.
+fn search_parallel<W, I>(
+    search: &InputSearch,
+    paths: I,
+    jobs: usize,
+    colors: Option<Colors>,
+    output: &mut Output<W>,
+    outcome: &mut Outcome,
+) -> io::Result<()>
+where
+    W: Write,
+    I: Iterator<Item = io::Result<PathBuf>> + Send,
+{
+    let config = search.config;
+    let queue = Mutex::new(paths.enumerate());
+    let stop = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..jobs {
+            let (sender, queue, stop, colors) = (sender.clone(), &queue, &stop, colors.clone());
+            scope.spawn(move || {
+                let mut buffer = Output::new(Vec::new(), config, search.matcher, colors);
+                while !stop.load(Ordering::Relaxed) {
+                    let next = queue.lock().unwrap_or_else(|e| e.into_inner()).next();
+                    let (index, path) = match next {
+                        Some(next) => next,
+                        None => break,
+                    };
+                    let result = search.search_path(path, &mut buffer);
+                    if sender.send((index, result, buffer.take_output())).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // Results that arrived before some of those due earlier, with `--sort path`.
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for (index, result, printed) in receiver {
+            pending.insert(index, (result, printed));
+            while let Some(first) = pending.first_entry() {
+                if config.sort == SortMode::Path && *first.key() != next {
+                    break;
+                }
+                let (result, printed) = first.remove();
+                next += 1;
+
+                match result {
+                    Err(e) => outcome.error(config, e),
+                    Ok(matches) => {
+                        outcome.matched |= matches > 0;
+                        if let Err(e) = output.write_input(&printed, matches) {
+                            stop.store(true, Ordering::Relaxed);
+                            return Err(e);
+                        }
+                    }
+                }
+                if config.quiet && outcome.matched {
+                    stop.store(true, Ordering::Relaxed);
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+/// What is needed to search one input after another.
+struct InputSearch<'a> {
+    config: &'a Config,
+    matcher: &'a Matcher,
+    searcher: Searcher,
+    with_filename: bool,
+}
 
-        let filename = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a file name"),
+impl InputSearch<'_> {
+    /// Opens and searches a file listed by `walk_inputs`, writing its results
+    /// to `output`. Returns how many matching lines it has.
+    fn search_path<W: Write>(
+        &self,
+        path: io::Result<PathBuf>,
+        output: &mut Output<W>,
+    ) -> Result<u64, InputError> {
+        let path = path.map_err(InputError::Open)?;
+        if path.as_os_str() == "-" {
+            let name = "(standard input)";
+            output.set_path(self.with_filename.then(|| name.to_string()));
+            return self
+                .search_input(io::stdin().lock(), Path::new(name), output)
+                .map_err(|e| InputError::Search(name.to_string(), e));
+        }
+
+        let file = File::open(&path).map_err(InputError::Open)?;
+        let name = path.display().to_string();
+        output.set_path(self.with_filename.then(|| name.clone()));
+        self.search_input(BufReader::new(file), &path, output)
+            .map_err(|e| InputError::Search(name, e))
+    }
+
+    /// Searches a single input, printing what the output mode of the config
+    /// asks for.
+    ///
+    /// Returns how many matching lines were found, counting a matching file
+    /// as one when only file names are printed.
+    ///
+    /// With `--json`, every input is searched line by line, whatever the
+    /// output mode.
+    fn search_input<R, W>(
+        &self,
+        reader: R,
+        path: &Path,
+        output: &mut Output<W>,
+    ) -> Result<u64, Interrupted>
+    where
+        R: BufRead,
+        W: Write,
+    {
+        let (config, matcher, searcher) = (self.config, self.matcher, &self.searcher);
+        if config.quiet {
+            return Ok(searcher.has_match(matcher, reader)? as u64);
+        }
+
+        let printer = match output {
+            Output::Text(printer) => printer,
+            Output::Json(json) => {
+                json.begin(path)?;
+                let matches = self.stream(reader, json)?;
+                json.end(matches)
+                    .map_err(|error| Interrupted { error, matches })?;
+                return Ok(matches);
+            }
         };
 
-        let case_sensitive = env::var("CASE_INSENSITIVE").is_err();
+        match config.output {
+            OutputMode::Lines => {}
+            OutputMode::Count => {
+                let count = searcher.count(matcher, reader)?;
+                printer.count(count).map_err(|error| Interrupted {
+                    error,
+                    matches: count,
+                })?;
+                return Ok(count);
+            }
+            OutputMode::FilesWithMatches => {
+                let found = searcher.has_match(matcher, reader)?;
+                if found {
+                    printer
+                        .file_name(&path.display().to_string())
+                        .map_err(|error| Interrupted { error, matches: 1 })?;
+                }
+                return Ok(found as u64);
+            }
+            OutputMode::FilesWithoutMatch => {
+                let found = searcher.has_match(matcher, reader)?;
+                if !found {
+                    printer.file_name(&path.display().to_string())?;
+                }
+                return Ok(found as u64);
+            }
+        }
 
-        Ok(Config {
-            query,
-            filename,
-            case_sensitive,
-        })
+        self.stream(reader, printer)
+    }
+
+    /// Searches `reader` line by line, passing the lines to print to `sink`.
+    fn stream<R: BufRead, S: Sink>(&self, reader: R, sink: &mut S) -> Result<u64, Interrupted> {
+        let mut counted = Counted { sink, matches: 0 };
+        self.searcher
+            .search_reader(self.matcher, reader, &mut counted)
+            .map_err(|error| Interrupted {
+                error,
+                matches: counted.matches,
+            })
     }
 }
 
-/// Runs the program using the config and search functions.
-///
-/// Performs the following operations:
-/// - reading from a given filename
-/// - searches for the given query with the appropriate search function
-/// - prints the results found
-/// - returns `Ok(())` if successful
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.filename)?;
-
-    let results = if config.case_sensitive {
-        search(&config.query, &contents)
-    } else {
-        search_case_insensitive(&config.query, &contents)
-    };
+/// Passes the lines of a search on to `sink`, counting the matching ones so
+/// that they are known even if the search fails partway.
+struct Counted<'a, S> {
+    sink: &'a mut S,
+    matches: u64,
+}
 
-    for line in results {
-        println!("{}", line);
+impl<S: Sink> Sink for Counted<'_, S> {
+    fn matched(&mut self, m: &LineMatch) -> io::Result<bool> {
+        self.matches += 1;
+        self.sink.matched(m)
     }
 
-    Ok(())
+    fn context(&mut self, line: &ContextLine) -> io::Result<bool> {
+        self.sink.context(line)
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        self.sink.context_break()
+    }
+}
+
+/// A search of an input that failed partway, after `matches` lines matched.
+#[derive(Debug)]
+struct Interrupted {
+    error: io::Error,
+    matches: u64,
+}
+
+impl From<io::Error> for Interrupted {
+    fn from(error: io::Error) -> Interrupted {
+        Interrupted { error, matches: 0 }
+    }
+}
+
+/// Why an input could not be searched.
+enum InputError {
+    /// It could not be listed or opened. The error names the path.
+    Open(io::Error),
+    /// Reading it or writing its results failed.
+    Search(String, Interrupted),
+}
+
+impl Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputError::Open(e) => write!(f, "{}", e),
+            InputError::Search(name, e) => write!(f, "{}: {}", name, e.error),
+        }
+    }
+}
+
+/// Where results are written: in grep's format, or as JSON Lines with `--json`.
+enum Output<W> {
+    Text(Printer<W>),
+    Json(JsonPrinter<W>),
+}
+
+impl<W: Write> Output<W> {
+    fn new(out: W, config: &Config, matcher: &Matcher, colors: Option<Colors>) -> Output<W> {
+        if config.json {
+            Output::Json(JsonPrinter::new(out, matcher))
+        } else {
+            Output::Text(Printer::new(out, config, matcher, colors))
+        }
+    }
+
+    /// Writes the results of a whole input, as written by another `Output`,
+    /// in which `matches` lines matched.
+    fn write_input(&mut self, output: &[u8], matches: u64) -> io::Result<()> {
+        match self {
+            Output::Text(printer) => printer.write_input(output),
+            Output::Json(json) => json.write_input(output, matches),
+        }
+    }
+
+    /// Sets the path printed before the lines of the next input, if any.
+    /// JSON events always have the path.
+    fn set_path(&mut self, path: Option<String>) {
+        if let Output::Text(printer) = self {
+            printer.set_path(path);
+        }
+    }
+}
+
+impl Output<Vec<u8>> {
+    /// Takes the results written so far.
+    fn take_output(&mut self) -> Vec<u8> {
+        match self {
+            Output::Text(printer) => printer.take_output(),
+            Output::Json(json) => json.take_output(),
+        }
+    }
+}
+
+/// What a search found, which decides the exit code of the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Outcome {
+    /// Whether any line matched.
+    pub matched: bool,
+    /// Whether an input could not be searched.
+    pub had_errors: bool,
+    /// Whether the search was run with `-q`.
+    pub quiet: bool,
+}
+
+impl Outcome {
+    /// Returns the exit code, following grep: 0 when a line matched, 1 when
+    /// none did and 2 when an error occurred. With `-q`, a match wins over
+    /// errors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minigrep_ag::Outcome;
+    ///
+    /// let outcome = Outcome { matched: true, had_errors: true, quiet: false };
+    /// assert_eq!(2, outcome.exit_code());
+    /// assert_eq!(0, Outcome { quiet: true, ..outcome }.exit_code());
+    /// assert_eq!(1, Outcome::default().exit_code());
+    /// ```
+    pub fn exit_code(&self) -> i32 {
+        if self.had_errors && !(self.quiet && self.matched) {
+            2
+        } else if self.matched {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// Records an error with an input, reporting it on stderr unless `-s` was given.
+    fn error(&mut self, config: &Config, message: impl Display) {
+        self.had_errors = true;
+        if !config.no_messages {
+            eprintln!("minigrep: {}", message);
+        }
+    }
+}
+
+/// Returns the stricter of two optional limits.
+fn min_limit(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Takes `matches` off the `--max-total` budget, if any, returning whether it
+/// is used up.
+fn take(remaining: &mut Option<u64>, matches: u64) -> bool {
+    match remaining {
+        Some(remaining) => {
+            *remaining = remaining.saturating_sub(matches);
+            *remaining == 0
+        }
+        None => false,
+    }
 }
 
 /// Searches the `query` in the `contents` given - case sensitive.
@@ -78,8 +557,24 @@ pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
         .collect()
 }
 
+/// Counts the lines of the `contents` given where the `query` is found - case sensitive.
+/// Unlike `search`, no vector of lines is built.
+///
+/// # Examples
+///
+/// ```
+/// let query = "the";
+/// let contents = "How public, like The Frog\nTo tell your name the livelong day\nTo the bog";
+///
+/// assert_eq!(2, minigrep_ag::count(query, contents))
+/// ```
+pub fn count(query: &str, contents: &str) -> usize {
+    contents.lines().filter(|line| line.contains(query)).count()
+}
+
 /// Searches the `query` in the `contents` given - case insensitives.
 /// Returns a vector of string slices representing the lines where the query is found.
+/// Case is compared with Unicode full case folding, so `straße` matches `STRASSE`.
 ///
 /// # Examples
 ///
@@ -91,9 +586,29 @@ pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
 /// assert_eq!(result, minigrep_ag::search_case_insensitive(query, contents))
 /// ```
 pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    let folder = CaseFolder::new(query);
     contents
         .lines()
-        .filter(|line| line.to_lowercase().contains(&query.to_lowercase()))
+        .filter(|line| folder.find_at(line.as_bytes(), 0).is_some())
+        .collect()
+}
+
+/// Searches the `contents` given for lines matching the regular expression `regex`.
+/// Returns a vector of string slices representing the lines where the expression matches.
+///
+/// # Examples
+///
+/// ```
+/// let regex = regex::Regex::new(r"^To (tell|say)").unwrap();
+/// let contents = "How public, like The Frog\nTo tell your name the livelong day";
+/// let result = vec!["To tell your name the livelong day"];
+///
+/// assert_eq!(result, minigrep_ag::search_regex(&regex, contents))
+/// ```
+pub fn search_regex<'a>(regex: &Regex, contents: &'a str) -> Vec<&'a str> {
+    contents
+        .lines()
+        .filter(|line| regex.is_match(line))
         .collect()
 }
 
@@ -113,6 +628,18 @@ Duct tape.";
         assert_eq!(vec!["safe, fast, productive."], search(query, contents));
     }
 
+    #[test]
+    fn count_matching_lines() {
+        let query = "duct";
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Duct tape.";
+
+        assert_eq!(1, count(query, contents));
+    }
+
     #[test]
     fn case_insensitive_search() {
         let query = "rUsT";
@@ -127,4 +654,109 @@ Trust me.";
             search_case_insensitive(query, contents)
         );
     }
+
+    #[test]
+    fn regex_search() {
+        let regex = Regex::new(r"^(ERROR|WARN)\b").unwrap();
+        let contents = "\
+ERROR disk full
+INFO all good
+WARN low memory
+INFO no ERROR here";
+
+        assert_eq!(
+            vec!["ERROR disk full", "WARN low memory"],
+            search_regex(&regex, contents)
+        );
+    }
+
+    #[test]
+    fn max_total_across_inputs() {
+        let dir = std::env::temp_dir().join(format!("minigrep-max-total-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths: Vec<_> = ["a", "b", "c"]
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                let contents = format!("needle {0}1\nhay\nneedle {0}2\nneedle {0}3\n", name);
+                std::fs::write(&path, contents).unwrap();
+                path
+            })
+            .collect();
+
+        // The budget runs out partway through `b`, or through `c` when `-m`
+        // leaves some of it for later files.
+        for (max_count, expected) in [(None, "a1 a2 a3 b1 b2"), (Some(2), "a1 a2 b1 b2 c1")] {
+            let config = Config {
+                patterns: vec!["needle".to_string()],
+                case_sensitive: true,
+                max_count,
+                max_total: Some(5),
+                ..Config::default()
+            };
+            let matcher = Matcher::new(&config).unwrap();
+            let mut search = InputSearch {
+                config: &config,
+                matcher: &matcher,
+                searcher: Searcher::new(&config),
+                with_filename: false,
+            };
+            let mut output = Output::new(Vec::new(), &config, &matcher, None);
+            let mut outcome = Outcome::default();
+            let paths = paths.iter().cloned().map(Ok);
+            search_serial(&mut search, paths, &mut output, &mut outcome).unwrap();
+
+            let printed = String::from_utf8(output.take_output()).unwrap();
+            let found: Vec<_> = printed.lines().map(|line| &line[7..]).collect();
+            assert_eq!(expected, found.join(" "));
+            assert!(outcome.matched);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn matches_before_a_broken_pipe() {
+        struct Closed;
+        impl Write for Closed {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // The first line printed is the first match, the count of both, or
+        // the JSON event opening the input, before any match.
+        for (output, json, matches) in [
+            (OutputMode::Lines, false, 1),
+            (OutputMode::Count, false, 2),
+            (OutputMode::Lines, true, 0),
+        ] {
+            let config = Config {
+                patterns: vec!["needle".to_string()],
+                case_sensitive: true,
+                output,
+                json,
+                ..Config::default()
+            };
+            let matcher = Matcher::new(&config).unwrap();
+            let search = InputSearch {
+                config: &config,
+                matcher: &matcher,
+                searcher: Searcher::new(&config),
+                with_filename: false,
+            };
+            let mut output = Output::new(Closed, &config, &matcher, None);
+            let e = search
+                .search_input(
+                    &b"hay\nneedle\nneedle\n"[..],
+                    Path::new("a.txt"),
+                    &mut output,
+                )
+                .unwrap_err();
+            assert_eq!(io::ErrorKind::BrokenPipe, e.error.kind());
+            assert_eq!(matches, e.matches);
+        }
+    }
 }
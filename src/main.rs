@@ -1,3 +1,4 @@
+use std::io::{self, Write};
 use std::{env, process};
 
 use minigrep_ag::Config;
@@ -5,11 +6,20 @@ use minigrep_ag::Config;
 fn main() {
     let config = Config::new(env::args()).unwrap_or_else(|err| {
         eprintln!("Problem parsing arguments: {}", err);
-        process::exit(1);
+        process::exit(2);
     });
 
-    if let Err(e) = minigrep_ag::run(config) {
-        eprintln!("Application error: {}", e);
-        process::exit(1);
+    if config.type_list {
+        // Like a search, stop silently if the reader goes away.
+        let _ = write!(io::stdout(), "{}", config.file_types);
+        process::exit(0);
+    }
+
+    match minigrep_ag::run(config) {
+        Ok(outcome) => process::exit(outcome.exit_code()),
+        Err(e) => {
+            eprintln!("Application error: {}", e);
+            process::exit(2);
+        }
     }
 }
@@ -0,0 +1,631 @@
+//! Line matching, compiled once from a `Config`.
+
+use std::error::Error;
+use std::ops::Range;
+use std::str;
+
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, Input, MatchKind};
+use memchr::memmem::Finder;
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::casefold::{self, CaseFolder};
+use crate::Config;
+
+/// Decides whether a line matches any of the patterns.
+///
+/// Lines are raw bytes so that files which are not valid UTF-8 can still be
+/// searched. When several literal patterns match at the same place, the
+/// longest match wins.
+///
+/// With `-w`, a match must not be preceded or followed by a word character:
+/// a letter, a digit or `_`, as defined by Unicode. Regular expressions use
+/// the Unicode `\w` class of the regex crate, which also counts combining
+/// marks and other connector punctuation as word characters, and never finds
+/// a word boundary next to invalid UTF-8.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    kind: Kind,
+    /// Where literal matches must start and end; regular expressions have
+    /// it compiled in.
+    boundary: Boundary,
+    /// With `-w` and several literals, finds every one of them that matches
+    /// at a given position: a shorter one may be a whole word where the
+    /// longest is not. Case-insensitive literals use their folders instead on
+    /// lines that are not ASCII.
+    anchored: Option<AhoCorasick>,
+    /// The capture group printed by `-o`, if not the whole match.
+    group: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    Literal(Box<Finder<'static>>),
+    /// Several literals, searched for all at once.
+    Literals(AhoCorasick),
+    CaseInsensitive {
+        folders: Vec<CaseFolder>,
+        /// Searches all the patterns at once on ASCII lines, when they are all
+        /// ASCII. Other lines may match through non-ASCII folds, such as the
+        /// Kelvin sign for `k`.
+        ascii: Option<AhoCorasick>,
+        /// Finds which patterns may match other lines, when there are several
+        /// of them, by searching for their foldings in a folded copy of the
+        /// line. Only those patterns are then searched for.
+        folded: Option<AhoCorasick>,
+    },
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Boundary {
+    Anywhere,
+    Word,
+    Line,
+}
+
+impl Matcher {
+    /// Compiles the patterns of `config`, failing if one is an invalid regular
+    /// expression or the group to print is not a capture group they have.
+    ///
+    /// Without any pattern, nothing matches.
+    pub fn new(config: &Config) -> Result<Matcher, Box<dyn Error>> {
+        let patterns = &config.patterns;
+        let boundary = if config.line_regexp {
+            Boundary::Line
+        } else if config.word_regexp {
+            Boundary::Word
+        } else {
+            Boundary::Anywhere
+        };
+
+        let kind = if patterns.is_empty() {
+            Kind::Literals(automaton(patterns, false)?)
+        } else if config.regex {
+            // A single pattern is left as it is, so that errors quote it as given.
+            let pattern = match patterns.as_slice() {
+                [pattern] => pattern.clone(),
+                _ => patterns
+                    .iter()
+                    .map(|pattern| format!("(?:{})", pattern))
+                    .collect::<Vec<_>>()
+                    .join("|"),
+            };
+            let pattern = match boundary {
+                Boundary::Anywhere => pattern,
+                Boundary::Word => format!(r"\b{{start-half}}(?:{})\b{{end-half}}", pattern),
+                Boundary::Line => format!(r"\A(?:{})\z", pattern),
+            };
+            let regex = RegexBuilder::new(&pattern)
+                .case_insensitive(!config.case_sensitive)
+                .build()?;
+            Kind::Regex(regex)
+        } else if !config.case_sensitive {
+            let ascii = if patterns.iter().all(|p| p.is_ascii()) {
+                Some(automaton(patterns, true)?)
+            } else {
+                None
+            };
+            let folded = if patterns.len() > 1 {
+                let folded: Vec<_> = patterns
+                    .iter()
+                    .map(|p| casefold::fold_bytes(p.as_bytes()))
+                    .collect();
+                Some(
+                    AhoCorasickBuilder::new()
+                        .match_kind(MatchKind::Standard)
+                        .build(folded)?,
+                )
+            } else {
+                None
+            };
+            let folders = patterns.iter().map(|p| CaseFolder::new(p)).collect();
+            Kind::CaseInsensitive {
+                folders,
+                ascii,
+                folded,
+            }
+        } else if patterns.len() == 1 {
+            Kind::Literal(Box::new(Finder::new(patterns[0].as_bytes()).into_owned()))
+        } else {
+            Kind::Literals(automaton(patterns, false)?)
+        };
+
+        let group = match (&config.group, &kind) {
+            (None, _) => None,
+            (Some(name), Kind::Regex(regex)) => Some(group_index(regex, name)?),
+            (Some(_), _) => return Err("--group requires --regex".into()),
+        };
+
+        let boundary = match kind {
+            Kind::Regex(_) => Boundary::Anywhere,
+            _ => boundary,
+        };
+
+        let anchored = match kind {
+            Kind::Literals(_) | Kind::CaseInsensitive { ascii: Some(_), .. }
+                if boundary == Boundary::Word && patterns.len() > 1 =>
+            {
+                let automaton = AhoCorasickBuilder::new()
+                    .match_kind(MatchKind::Standard)
+                    .ascii_case_insensitive(!config.case_sensitive)
+                    .build(patterns)?;
+                Some(automaton)
+            }
+            _ => None,
+        };
+
+        Ok(Matcher {
+            kind,
+            boundary,
+            anchored,
+            group,
+        })
+    }
+
+    /// Returns whether any pattern is found in `line`.
+    pub fn is_match(&self, line: &[u8]) -> bool {
+        match &self.kind {
+            Kind::Regex(regex) => regex.is_match(line),
+            _ => self.find(line).is_some(),
+        }
+    }
+
+    /// Returns the byte range of the first match in `line`.
+    pub fn find(&self, line: &[u8]) -> Option<Range<usize>> {
+        self.find_at(line, 0)
+    }
+
+    /// Returns the byte range of the first match in `line` starting at or after `start`.
+    pub fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        self.find_from(line, start, &mut Progress::default())
+    }
+
+    /// Like `find_at`, reusing what earlier searches of `line` found.
+    /// `start` must not be before the start of those searches.
+    fn find_from(
+        &self,
+        line: &[u8],
+        start: usize,
+        progress: &mut Progress,
+    ) -> Option<Range<usize>> {
+        let mut at = start;
+        loop {
+            let range = self.find_anywhere(line, at, progress)?;
+            match self.boundary {
+                Boundary::Anywhere => return Some(range),
+                // The longest match at the start of the line is the only one
+                // that can cover all of it.
+                Boundary::Line if range == (0..line.len()) => return Some(range),
+                Boundary::Line => return None,
+                Boundary::Word
+                    if !is_word_before(line, range.start) && !is_word_after(line, range.end) =>
+                {
+                    return Some(range)
+                }
+                Boundary::Word if range.start == line.len() => return None,
+                Boundary::Word => {
+                    if !is_word_before(line, range.start) {
+                        let end = self
+                            .ends_at(line, range.start)
+                            .into_iter()
+                            .find(|&end| !is_word_after(line, end));
+                        if let Some(end) = end {
+                            return Some(range.start..end);
+                        }
+                    }
+                    at = range.start + char_at(line, range.start).map_or(1, char::len_utf8)
+                }
+            }
+        }
+    }
+
+    /// Returns where the matches of every pattern starting at `start` end,
+    /// longest first.
+    fn ends_at(&self, line: &[u8], start: usize) -> Vec<usize> {
+        let mut ends: Vec<_> = match (&self.kind, &self.anchored) {
+            (Kind::CaseInsensitive { folders, .. }, anchored)
+                if anchored.is_none() || !line.is_ascii() =>
+            {
+                folders
+                    .iter()
+                    .filter_map(|folder| folder.match_at(line, start))
+                    .collect()
+            }
+            (_, Some(automaton)) => {
+                let end = line.len().min(start + automaton.max_pattern_len());
+                automaton
+                    .find_overlapping_iter(Input::new(line).range(start..end))
+                    .filter(|m| m.start() == start)
+                    .map(|m| m.end())
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+        ends.sort_unstable_by(|a, b| b.cmp(a));
+        ends
+    }
+
+    /// Like `find_at`, ignoring the boundary.
+    fn find_anywhere(
+        &self,
+        line: &[u8],
+        start: usize,
+        progress: &mut Progress,
+    ) -> Option<Range<usize>> {
+        match &self.kind {
+            Kind::Literal(finder) => finder
+                .find(&line[start..])
+                .map(|i| start + i..start + i + finder.needle().len()),
+            Kind::Literals(automaton) => automaton
+                .find(Input::new(line).range(start..))
+                .map(|m| m.range()),
+            Kind::CaseInsensitive {
+                ascii: Some(automaton),
+                ..
+            } if line.is_ascii() => automaton
+                .find(Input::new(line).range(start..))
+                .map(|m| m.range()),
+            Kind::CaseInsensitive { folders, .. } if folders.len() == 1 => {
+                folders[0].find_at(line, start)
+            }
+            Kind::CaseInsensitive {
+                folders, folded, ..
+            } => {
+                let next = progress.next.get_or_insert_with(|| {
+                    let mut candidates: Vec<_> = match folded {
+                        Some(automaton) => automaton
+                            .find_overlapping_iter(&casefold::fold_bytes(line))
+                            .map(|m| m.pattern().as_usize())
+                            .collect(),
+                        None => (0..folders.len()).collect(),
+                    };
+                    candidates.sort_unstable();
+                    candidates.dedup();
+                    candidates
+                        .into_iter()
+                        .map(|i| (i, folders[i].find_at(line, start)))
+                        .collect()
+                });
+                for (i, found) in next.iter_mut() {
+                    if found.as_ref().is_some_and(|range| range.start < start) {
+                        *found = folders[*i].find_at(line, start);
+                    }
+                }
+                next.iter()
+                    .filter_map(|(_, found)| found.clone())
+                    .min_by_key(|range| (range.start, usize::MAX - range.end))
+            }
+            Kind::Regex(regex) => regex.find_at(line, start).map(|m| m.range()),
+        }
+    }
+
+    /// Returns where the first possible match in `haystack`, which may hold
+    /// many lines, starts at or after `start`.
+    ///
+    /// Case-sensitive literals are searched for directly, with SIMD where the
+    /// CPU has it, so that lines without a match are skipped without even
+    /// being split. Other patterns can only be tried line by line: `start`
+    /// itself is returned. Either way, the line around the returned position
+    /// must still be checked with `find`.
+    pub fn find_candidate(&self, haystack: &[u8], start: usize) -> Option<usize> {
+        match &self.kind {
+            Kind::Literal(finder) => finder.find(&haystack[start..]).map(|i| start + i),
+            Kind::Literals(automaton) => automaton
+                .find(Input::new(haystack).range(start..))
+                .map(|m| m.start()),
+            _ => Some(start),
+        }
+    }
+
+    /// Returns every non-overlapping, non-empty match in `line`, from left to right.
+    pub fn find_iter<'a>(&'a self, line: &'a [u8]) -> impl Iterator<Item = Range<usize>> + 'a {
+        let mut start = 0;
+        let mut progress = Progress::default();
+        std::iter::from_fn(move || {
+            while start <= line.len() {
+                let range = self.find_from(line, start, &mut progress)?;
+                if range.is_empty() {
+                    start = range.end + 1;
+                    continue;
+                }
+                start = range.end;
+                return Some(range);
+            }
+            None
+        })
+    }
+
+    /// Returns the parts of `line` that `-o` prints: every non-empty match, or
+    /// the chosen capture group of every match where that group took part.
+    pub fn only_matching(&self, line: &[u8]) -> Vec<Range<usize>> {
+        match (&self.kind, self.group) {
+            (Kind::Regex(regex), Some(group)) => regex
+                .captures_iter(line)
+                .filter_map(|captures| captures.get(group))
+                .map(|m| m.range())
+                .filter(|range| !range.is_empty())
+                .collect(),
+            _ => self.find_iter(line).collect(),
+        }
+    }
+}
+
+/// What the search for one match in a line leaves for the search for the next.
+#[derive(Default)]
+struct Progress {
+    /// With several case-insensitive patterns on a line that the ASCII
+    /// automaton cannot search: the patterns that may match somewhere in the
+    /// line, each with its first match from where it was last searched.
+    /// Folding the line once to find them serves every match in it.
+    next: Option<Vec<(usize, Option<Range<usize>>)>>,
+}
+
+/// Resolves a capture group given by number or by name.
+fn group_index(regex: &Regex, name: &str) -> Result<usize, Box<dyn Error>> {
+    let index = match name.parse::<usize>() {
+        Ok(index) if index < regex.captures_len() => Some(index),
+        Ok(_) => None,
+        Err(_) => regex.capture_names().position(|n| n == Some(name)),
+    };
+    index.ok_or_else(|| format!("no capture group '{}' in the pattern", name).into())
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Returns whether the character ending at `at` is a word character.
+fn is_word_before(line: &[u8], at: usize) -> bool {
+    (1..=at.min(4))
+        .find_map(|width| str::from_utf8(&line[at - width..at]).ok())
+        .and_then(|s| s.chars().next())
+        .is_some_and(is_word_char)
+}
+
+/// Returns whether the character starting at `at` is a word character.
+fn is_word_after(line: &[u8], at: usize) -> bool {
+    char_at(line, at).is_some_and(is_word_char)
+}
+
+/// Decodes the character starting at `at`, if it is valid UTF-8.
+fn char_at(line: &[u8], at: usize) -> Option<char> {
+    let bytes = &line[at..line.len().min(at + 4)];
+    let valid = match str::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(e) => str::from_utf8(&bytes[..e.valid_up_to()]).ok()?,
+    };
+    valid.chars().next()
+}
+
+/// Builds an automaton finding the leftmost, longest of `patterns`.
+fn automaton(
+    patterns: &[String],
+    ascii_case_insensitive: bool,
+) -> Result<AhoCorasick, Box<dyn Error>> {
+    let automaton = AhoCorasickBuilder::new()
+        .match_kind(MatchKind::LeftmostLongest)
+        .ascii_case_insensitive(ascii_case_insensitive)
+        .build(patterns)?;
+    Ok(automaton)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(query: &str, case_sensitive: bool, regex: bool) -> Matcher {
+        let config = Config {
+            patterns: vec![query.to_string()],
+            case_sensitive,
+            regex,
+            ..Config::default()
+        };
+        Matcher::new(&config).unwrap()
+    }
+
+    #[test]
+    fn literal_and_case_insensitive() {
+        assert!(matcher("duct", true, false).is_match(b"productive"));
+        assert!(!matcher("Duct", true, false).is_match(b"productive"));
+        assert!(matcher("Duct", false, false).is_match(b"productive"));
+        assert!(matcher("", true, false).is_match(b""));
+    }
+
+    #[test]
+    fn match_offsets() {
+        let line = b"\xc3\xa9\xffductive";
+
+        assert_eq!(
+            Some(4..8),
+            matcher("duct", true, false).find(b"prodductive")
+        );
+        assert_eq!(Some(3..7), matcher("DUCT", false, false).find(line));
+        assert_eq!(
+            Some(1..8),
+            matcher("STRASSE", false, false).find("_straße".as_bytes())
+        );
+        assert_eq!(
+            Some(3..5),
+            matcher("\u{e9}", false, false).find("caf\u{c9}".as_bytes())
+        );
+        assert_eq!(Some(2..5), matcher("b+c", true, true).find(b"a bbc"));
+    }
+
+    #[test]
+    fn all_matches() {
+        let spans: Vec<_> = matcher("o", false, false).find_iter(b"foo bOo").collect();
+        assert_eq!(vec![1..2, 2..3, 5..6, 6..7], spans);
+
+        let spans: Vec<_> = matcher("x*", true, true).find_iter(b"axxbx").collect();
+        assert_eq!(vec![1..3, 4..5], spans);
+    }
+
+    #[test]
+    fn capture_groups() {
+        let mut config = Config {
+            patterns: vec![r"id=(\d+)(?P<suffix>[a-z])?".to_string()],
+            regex: true,
+            case_sensitive: true,
+            ..Config::default()
+        };
+        let line = b"id=12a, id=7, id=";
+
+        assert_eq!(
+            vec![0..6, 8..12],
+            Matcher::new(&config).unwrap().only_matching(line)
+        );
+
+        config.group = Some("1".to_string());
+        assert_eq!(
+            vec![3..5, 11..12],
+            Matcher::new(&config).unwrap().only_matching(line)
+        );
+
+        config.group = Some("suffix".to_string());
+        assert_eq!(
+            vec![5..6],
+            Matcher::new(&config).unwrap().only_matching(line)
+        );
+
+        config.group = Some("3".to_string());
+        assert!(Matcher::new(&config).is_err());
+        config.regex = false;
+        config.group = Some("0".to_string());
+        assert!(Matcher::new(&config).is_err());
+    }
+
+    #[test]
+    fn multiple_patterns() {
+        let matcher = |patterns: &[&str], case_sensitive, regex| {
+            let config = Config {
+                patterns: patterns.iter().map(|p| p.to_string()).collect(),
+                case_sensitive,
+                regex,
+                ..Config::default()
+            };
+            Matcher::new(&config).unwrap()
+        };
+        let line = "the Straße is stressful".as_bytes();
+
+        let literals = matcher(&["stress", "stressful", "the"], true, false);
+        assert_eq!(
+            vec![0..3, 15..24],
+            literals.find_iter(line).collect::<Vec<_>>()
+        );
+
+        let ascii = matcher(&["STRESS", "THE", "KEY"], false, false);
+        assert_eq!(
+            vec![0..3, 15..21],
+            ascii.find_iter(line).collect::<Vec<_>>()
+        );
+        assert!(ascii.is_match("\u{212a}ey".as_bytes()));
+
+        let folded = matcher(&["strasse", "straßeX", "is"], false, false);
+        assert_eq!(
+            vec![4..11, 12..14],
+            folded.find_iter(line).collect::<Vec<_>>()
+        );
+
+        let regex = matcher(&["a|b", "^x"], true, true);
+        assert!(regex.is_match(b"xyz") && regex.is_match(b"yb") && !regex.is_match(b"yx"));
+
+        let many: Vec<_> = (0..3000).map(|i| format!("word{}", i)).collect();
+        let many: Vec<_> = many.iter().map(String::as_str).collect();
+        let many = matcher(&many, false, false);
+        assert!(many.is_match("café WORD2999".as_bytes()));
+        assert!(many.is_match("café wORD12 \u{212a}".as_bytes()));
+        assert!(!many.is_match("café word".as_bytes()));
+        assert_eq!(
+            vec![6..11, 12..20],
+            many.find_iter("café WORD1 word2500".as_bytes())
+                .collect::<Vec<_>>()
+        );
+
+        assert!(!matcher(&[], true, false).is_match(b"anything"));
+        assert!(!matcher(&[], false, true).is_match(b""));
+    }
+
+    #[test]
+    fn many_matches_on_a_long_line() {
+        let line = format!("{}ZZ", "é AB ".repeat(20_000));
+        for word_regexp in [false, true] {
+            let config = Config {
+                patterns: vec!["ab".to_string(), "zz".to_string(), "b é".to_string()],
+                case_sensitive: false,
+                word_regexp,
+                ..Config::default()
+            };
+            let matcher = Matcher::new(&config).unwrap();
+            let spans: Vec<_> = matcher.find_iter(line.as_bytes()).collect();
+            assert_eq!(20_001, spans.len());
+            assert_eq!(Some(&(3..5)), spans.first());
+            assert_eq!(Some(&(line.len() - 2..line.len())), spans.last());
+        }
+    }
+
+    #[test]
+    fn word_and_line_boundaries() {
+        let bounded = |pattern: &str, case_sensitive, regex, line_regexp| {
+            let config = Config {
+                patterns: vec![pattern.to_string()],
+                case_sensitive,
+                regex,
+                word_regexp: true,
+                line_regexp,
+                ..Config::default()
+            };
+            Matcher::new(&config).unwrap()
+        };
+
+        for (case_sensitive, regex) in [(true, false), (false, false), (true, true), (false, true)]
+        {
+            let word = bounded("the", case_sensitive, regex, false);
+            assert!(!word.is_match(b"Theirs and other"));
+            assert_eq!(Some(10..13), word.find(b"other... (the)"));
+            assert_eq!(Some(13..16), word.find("théthe the_ the".as_bytes()));
+
+            let line = bounded("the", case_sensitive, regex, true);
+            assert!(line.is_match(b"the"));
+            assert!(!line.is_match(b"the end"));
+        }
+
+        assert!(bounded("the", true, false, false).is_match(b"\xffthe\xff"));
+        assert!(!bounded("the", true, true, false).is_match(b"\xffthe\xff"));
+
+        let word = bounded("é", false, false, false);
+        assert_eq!(None, word.find("caféÉ".as_bytes()));
+        assert_eq!(Some(9..11), word.find("caféÉ, É".as_bytes()));
+        assert!(!bounded("the", false, false, false).is_match("THEè".as_bytes()));
+
+        for case_sensitive in [true, false] {
+            let config = Config {
+                patterns: vec!["foo b".to_string(), "foo".to_string(), "fo".to_string()],
+                case_sensitive,
+                word_regexp: true,
+                ..Config::default()
+            };
+            let words = Matcher::new(&config).unwrap();
+            assert_eq!(Some(0..3), words.find(b"foo bar"));
+            assert_eq!(Some(0..3), words.find("foo bär".as_bytes()));
+            assert_eq!(Some(0..5), words.find(b"foo b"));
+            assert_eq!(None, words.find(b"foox bar"));
+        }
+        let config = Config {
+            patterns: vec!["stra".to_string(), "straße".to_string()],
+            case_sensitive: false,
+            word_regexp: true,
+            ..Config::default()
+        };
+        assert_eq!(
+            Some(0..4),
+            Matcher::new(&config).unwrap().find("STRA SSEN".as_bytes())
+        );
+        assert!(bounded("THE", false, false, true).is_match("thE".as_bytes()));
+    }
+
+    #[test]
+    fn regex_on_invalid_utf8() {
+        assert!(matcher("^ERROR|WARN", true, true).is_match(b"\xffWARN"));
+        assert!(matcher("warn", false, true).is_match(b"WARN \xff"));
+    }
+}
@@ -0,0 +1,335 @@
+//! Writes search results in grep's output format.
+
+use std::io::{self, Write};
+use std::ops::Range;
+
+use crate::color::{self, Colors};
+use crate::matcher::Matcher;
+use crate::searcher::{ContextLine, LineMatch, Sink};
+use crate::Config;
+
+/// A `Sink` that writes each matching line, prefixed with its path and
+/// position as requested by the config.
+///
+/// Context lines use `-` instead of `:` after each prefix, and groups of lines
+/// that are not contiguous are separated by a `--` line. With colors, every
+/// match in a matching line is highlighted.
+pub struct Printer<W> {
+    out: W,
+    matcher: Matcher,
+    colors: Option<Colors>,
+    path: Option<String>,
+    line_number: bool,
+    column: bool,
+    byte_offset: bool,
+    has_context: bool,
+    null: bool,
+    only_matching: bool,
+    /// Whether any line has been written, across all inputs.
+    printed: bool,
+    /// Whether a `--` is due before the next line, because a new input started.
+    pending_break: bool,
+}
+
+impl<W: Write> Printer<W> {
+    pub fn new(out: W, config: &Config, matcher: &Matcher, colors: Option<Colors>) -> Printer<W> {
+        Printer {
+            out,
+            matcher: matcher.clone(),
+            colors,
+            path: None,
+            line_number: config.line_number,
+            column: config.column,
+            byte_offset: config.byte_offset,
+            // Like grep, `-o` prints no context lines.
+            has_context: !config.only_matching
+                && (config.before_context > 0 || config.after_context > 0),
+            null: config.null,
+            only_matching: config.only_matching,
+            printed: false,
+            pending_break: false,
+        }
+    }
+
+    /// Sets the path printed before the lines of the next input, if any.
+    pub fn set_path(&mut self, path: Option<String>) {
+        self.path = path;
+        self.pending_break = self.has_context && self.printed;
+    }
+
+    /// Writes the prefixes of a line, each followed by `separator`.
+    fn write_prefix(
+        &mut self,
+        line_number: u64,
+        column: Option<usize>,
+        byte_offset: u64,
+        separator: char,
+    ) -> io::Result<()> {
+        if self.pending_break {
+            self.pending_break = false;
+            self.write_break()?;
+        }
+        self.printed = true;
+
+        self.write_path(separator)?;
+        if self.line_number {
+            self.write_colored(line_number.to_string().as_bytes(), |c| &c.line_number)?;
+            self.write_separator(separator)?;
+        }
+        if let Some(column) = column.filter(|_| self.column) {
+            self.write_colored(column.to_string().as_bytes(), |c| &c.line_number)?;
+            self.write_separator(separator)?;
+        }
+        if self.byte_offset {
+            self.write_colored(byte_offset.to_string().as_bytes(), |c| &c.byte_offset)?;
+            self.write_separator(separator)?;
+        }
+        Ok(())
+    }
+
+    /// Writes every matched part of a line on its own line, for `-o`.
+    ///
+    /// Each part gets the prefixes of its line, with its own column and byte offset.
+    fn write_only_matching(&mut self, m: &LineMatch) -> io::Result<()> {
+        for part in self.matcher.only_matching(m.line) {
+            let byte_offset = m.byte_offset + part.start as u64;
+            self.write_prefix(m.line_number, Some(part.start + 1), byte_offset, ':')?;
+            self.write_colored(&m.line[part], |c| &c.matched)?;
+            self.out.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// Writes a matching line, highlighting every match when coloring.
+    fn write_matched_line(&mut self, line: &[u8], span: &Range<usize>) -> io::Result<()> {
+        // An empty span is an inverted match, which has nothing to highlight.
+        if self.colors.is_none() || span.is_empty() {
+            return self.out.write_all(line);
+        }
+
+        let mut last = 0;
+        let spans: Vec<_> = self.matcher.find_iter(line).collect();
+        for span in spans {
+            self.out.write_all(&line[last..span.start])?;
+            self.write_colored(&line[span.clone()], |c| &c.matched)?;
+            last = span.end;
+        }
+        self.out.write_all(&line[last..])
+    }
+
+    /// Writes the current path, if any, followed by `separator` or by a NUL
+    /// byte with `--null`.
+    fn write_path(&mut self, separator: char) -> io::Result<()> {
+        if let Some(path) = &self.path {
+            write_colored(&mut self.out, self.colors.as_ref(), path.as_bytes(), |c| {
+                &c.path
+            })?;
+            if self.null {
+                self.out.write_all(b"\0")?;
+            } else {
+                self.write_separator(separator)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_separator(&mut self, separator: char) -> io::Result<()> {
+        self.write_colored(&[separator as u8], |c| &c.separator)
+    }
+
+    fn write_break(&mut self) -> io::Result<()> {
+        self.write_colored(b"--", |c| &c.separator)?;
+        self.out.write_all(b"\n")
+    }
+
+    fn write_colored(&mut self, text: &[u8], sgr: fn(&Colors) -> &String) -> io::Result<()> {
+        write_colored(&mut self.out, self.colors.as_ref(), text, sgr)
+    }
+
+    /// Writes how many lines of the current input matched.
+    pub fn count(&mut self, count: u64) -> io::Result<()> {
+        self.write_path(':')?;
+        writeln!(self.out, "{}", count)
+    }
+
+    /// Writes the name of a file on its own, for `-l` and `-L`.
+    pub fn file_name(&mut self, path: &str) -> io::Result<()> {
+        self.write_colored(path.as_bytes(), |c| &c.path)?;
+        self.out.write_all(if self.null { b"\0" } else { b"\n" })
+    }
+
+    /// Writes the output of a whole input, as printed by another printer,
+    /// separating it from what came before when printing context.
+    pub fn write_input(&mut self, output: &[u8]) -> io::Result<()> {
+        if output.is_empty() {
+            return Ok(());
+        }
+        if self.has_context && self.printed {
+            self.write_break()?;
+        }
+        self.printed = true;
+        self.out.write_all(output)
+    }
+}
+
+impl Printer<Vec<u8>> {
+    /// Takes what was printed so far, starting afresh as if nothing had been.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        self.printed = false;
+        self.pending_break = false;
+        std::mem::take(&mut self.out)
+    }
+}
+
+impl<W: Write> Sink for Printer<W> {
+    fn matched(&mut self, m: &LineMatch) -> io::Result<bool> {
+        if self.only_matching {
+            self.write_only_matching(m)?;
+            return Ok(true);
+        }
+        self.write_prefix(m.line_number, Some(m.column()), m.byte_offset, ':')?;
+        self.write_matched_line(m.line, &m.span)?;
+        self.out.write_all(b"\n")?;
+        Ok(true)
+    }
+
+    fn context(&mut self, c: &ContextLine) -> io::Result<bool> {
+        if !self.has_context {
+            return Ok(true);
+        }
+        self.write_prefix(c.line_number, None, c.byte_offset, '-')?;
+        self.out.write_all(c.line)?;
+        self.out.write_all(b"\n")?;
+        Ok(true)
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        if !self.has_context {
+            return Ok(());
+        }
+        self.write_break()
+    }
+}
+
+/// Writes `text`, in the color picked by `sgr` when there are `colors`.
+fn write_colored<W: Write>(
+    out: &mut W,
+    colors: Option<&Colors>,
+    text: &[u8],
+    sgr: fn(&Colors) -> &String,
+) -> io::Result<()> {
+    match colors {
+        Some(colors) => {
+            out.write_all(color::start(sgr(colors)).as_bytes())?;
+            out.write_all(text)?;
+            out.write_all(color::END.as_bytes())
+        }
+        None => out.write_all(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::searcher::Searcher;
+
+    fn print(config: &Config, colors: Option<Colors>, contents: &str) -> String {
+        let matcher = Matcher::new(config).unwrap();
+        let mut out = Vec::new();
+        let mut printer = Printer::new(&mut out, config, &matcher, colors);
+        printer.set_path(Some("poem.txt".to_string()));
+        Searcher::new(config)
+            .search_reader(&matcher, contents.as_bytes(), &mut printer)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn context_separators() {
+        let config = Config {
+            patterns: vec!["x".to_string()],
+            line_number: true,
+            after_context: 1,
+            ..Config::default()
+        };
+
+        assert_eq!(
+            "poem.txt:1:x\npoem.txt-2-a\n--\npoem.txt:4:x\n",
+            print(&config, None, "x\na\nb\nx\n")
+        );
+    }
+
+    #[test]
+    fn null_after_path() {
+        let config = Config {
+            patterns: vec!["x".to_string()],
+            line_number: true,
+            null: true,
+            ..Config::default()
+        };
+
+        assert_eq!("poem.txt\x002:x\n", print(&config, None, "a\nx\n"));
+    }
+
+    #[test]
+    fn only_matching_parts() {
+        let config = Config {
+            patterns: vec!["o+".to_string()],
+            regex: true,
+            case_sensitive: true,
+            only_matching: true,
+            column: true,
+            line_number: true,
+            before_context: 1,
+            ..Config::default()
+        };
+
+        assert_eq!(
+            "poem.txt:2:2:oo\npoem.txt:2:6:oo\n",
+            print(&config, None, "abc\nfoo boo\n")
+        );
+    }
+
+    #[test]
+    fn whole_inputs() {
+        let config = Config {
+            patterns: vec!["x".to_string()],
+            before_context: 1,
+            ..Config::default()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+        let mut worker = Printer::new(Vec::new(), &config, &matcher, None);
+        let mut out = Vec::new();
+        let mut printer = Printer::new(&mut out, &config, &matcher, None);
+
+        for contents in ["a\nx\n", "b\n", "x\n"] {
+            worker.set_path(None);
+            Searcher::new(&config)
+                .search_reader(&matcher, contents.as_bytes(), &mut worker)
+                .unwrap();
+            printer.write_input(&worker.take_output()).unwrap();
+        }
+
+        assert_eq!("a\nx\n--\nx\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn highlights_every_match() {
+        let config = Config {
+            patterns: vec!["o".to_string()],
+            ..Config::default()
+        };
+        let colors = Colors {
+            path: "1".to_string(),
+            separator: "2".to_string(),
+            matched: "3".to_string(),
+            ..Colors::default()
+        };
+
+        assert_eq!(
+            "\x1b[1m\x1b[Kpoem.txt\x1b[m\x1b[K\x1b[2m\x1b[K:\x1b[m\x1b[K\
+             d\x1b[3m\x1b[Ko\x1b[m\x1b[Kg\x1b[3m\x1b[KO\x1b[m\x1b[K\n",
+            print(&config, Some(colors), "dogO\n")
+        );
+    }
+}
@@ -0,0 +1,648 @@
+//! Streaming search over any `BufRead`.
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+use std::ops::Range;
+
+use memchr::{memchr, memchr_iter, memrchr};
+
+use crate::matcher::Matcher;
+use crate::Config;
+
+/// A matching line and where it was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMatch<'a> {
+    /// The line, without its line terminator.
+    pub line: &'a [u8],
+    /// The 1-based number of the line.
+    pub line_number: u64,
+    /// The offset of the start of the line from the start of the input, in bytes.
+    pub byte_offset: u64,
+    /// The byte range of the first match within `line`; empty for inverted matches.
+    pub span: Range<usize>,
+}
+
+impl LineMatch<'_> {
+    /// The 1-based column of the first match, counted in bytes.
+    pub fn column(&self) -> usize {
+        self.span.start + 1
+    }
+}
+
+/// Receives matching lines from `search_reader` as soon as they are found.
+///
+/// Closures of the form `FnMut(&LineMatch) -> io::Result<bool>` are sinks too.
+pub trait Sink {
+    /// Called with each matching line.
+    ///
+    /// Returns whether the search should carry on.
+    fn matched(&mut self, m: &LineMatch) -> io::Result<bool>;
+
+    /// Called with each context line around a match.
+    ///
+    /// Returns whether the search should carry on.
+    fn context(&mut self, _line: &ContextLine) -> io::Result<bool> {
+        Ok(true)
+    }
+
+    /// Called between two groups of reported lines that are not contiguous.
+    fn context_break(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<F> Sink for F
+where
+    F: FnMut(&LineMatch) -> io::Result<bool>,
+{
+    fn matched(&mut self, m: &LineMatch) -> io::Result<bool> {
+        self(m)
+    }
+}
+
+/// A line printed around a match, when context is asked for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextLine<'a> {
+    /// The line, without its line terminator.
+    pub line: &'a [u8],
+    /// The 1-based number of the line.
+    pub line_number: u64,
+    /// The offset of the start of the line from the start of the input, in bytes.
+    pub byte_offset: u64,
+}
+
+/// A line read from the input, kept with its position.
+#[derive(Default)]
+struct Buffered {
+    /// The raw line, including its line terminator.
+    bytes: Vec<u8>,
+    line_number: u64,
+    byte_offset: u64,
+}
+
+impl Buffered {
+    fn as_context(&self) -> ContextLine<'_> {
+        ContextLine {
+            line: trim_line_terminator(&self.bytes),
+            line_number: self.line_number,
+            byte_offset: self.byte_offset,
+        }
+    }
+}
+
+/// Searches inputs line by line with the given options.
+#[derive(Debug, Clone, Default)]
+pub struct Searcher {
+    /// How many lines to report before each match.
+    pub before_context: usize,
+    /// How many lines to report after each match.
+    pub after_context: usize,
+    /// Report the lines that do not match instead of the ones that do.
+    pub invert_match: bool,
+    /// Search whole blocks of the input for the patterns, only looking for
+    /// line boundaries around each hit. Used when neither context nor
+    /// inverted matches are asked for.
+    pub fixed_strings: bool,
+    /// Stop after this many matching lines, once their trailing context has
+    /// been reported.
+    pub max_count: Option<u64>,
+}
+
+impl Searcher {
+    /// Builds a `Searcher` with the options of `config`.
+    pub fn new(config: &Config) -> Searcher {
+        Searcher {
+            before_context: config.before_context,
+            after_context: config.after_context,
+            invert_match: config.invert_match,
+            fixed_strings: config.fixed_strings,
+            max_count: config.max_count,
+        }
+    }
+
+    /// Searches `reader` one line at a time, handing every matching line to
+    /// `sink` together with the requested context lines.
+    ///
+    /// Only the current line and up to `before_context` previous lines are
+    /// held in memory, so arbitrarily large inputs are searched in constant
+    /// memory. Overlapping context is reported once, and `sink` is told about
+    /// each gap between non-contiguous groups of lines. Both `\n` and `\r\n`
+    /// line endings are accepted.
+    ///
+    /// Reading stops as soon as `max_count` matching lines and their trailing
+    /// context have been reported; lines that match within that context are
+    /// reported as context. Returns how many matching lines were reported.
+    pub fn search_reader<R, S>(
+        &self,
+        matcher: &Matcher,
+        mut reader: R,
+        sink: &mut S,
+    ) -> io::Result<u64>
+    where
+        R: BufRead,
+        S: Sink + ?Sized,
+    {
+        let mut matches = 0;
+        if self.is_limit_reached(matches) {
+            return Ok(matches);
+        }
+
+        let has_context = self.before_context > 0 || self.after_context > 0;
+        if self.fixed_strings && !self.invert_match && !has_context {
+            search_blocks(matcher, reader, |m| {
+                matches += 1;
+                Ok(sink.matched(m)? && !self.is_limit_reached(matches))
+            })?;
+            return Ok(matches);
+        }
+
+        // A ring of the lines just before the current one, oldest first.
+        let mut before: VecDeque<Buffered> = VecDeque::with_capacity(self.before_context);
+        let mut current = Buffered::default();
+        let mut after_left = 0;
+        let mut last_reported: Option<u64> = None;
+        let mut line_number = 0;
+        let mut byte_offset = 0;
+
+        loop {
+            current.bytes.clear();
+            let read = reader.read_until(b'\n', &mut current.bytes)?;
+            if read == 0 {
+                return Ok(matches);
+            }
+            line_number += 1;
+            current.line_number = line_number;
+            current.byte_offset = byte_offset;
+            byte_offset += read as u64;
+
+            let line = trim_line_terminator(&current.bytes);
+            let found = if self.is_limit_reached(matches) {
+                None
+            } else {
+                self.find(matcher, line)
+            };
+            if let Some(span) = found {
+                let first = before.front().map_or(line_number, |b| b.line_number);
+                if has_context && last_reported.is_some_and(|last| first > last + 1) {
+                    sink.context_break()?;
+                }
+                for buffered in before.drain(..) {
+                    if !sink.context(&buffered.as_context())? {
+                        return Ok(matches);
+                    }
+                }
+
+                let m = LineMatch {
+                    line,
+                    line_number,
+                    byte_offset: current.byte_offset,
+                    span,
+                };
+                matches += 1;
+                if !sink.matched(&m)? {
+                    return Ok(matches);
+                }
+                after_left = self.after_context;
+                last_reported = Some(line_number);
+            } else if after_left > 0 {
+                after_left -= 1;
+                last_reported = Some(line_number);
+                if !sink.context(&current.as_context())? {
+                    return Ok(matches);
+                }
+            } else if self.before_context > 0 {
+                // Recycle the oldest buffer once the ring is full.
+                let recycled = if before.len() == self.before_context {
+                    before.pop_front().unwrap_or_default()
+                } else {
+                    Buffered::default()
+                };
+                before.push_back(std::mem::replace(&mut current, recycled));
+            }
+
+            if after_left == 0 && self.is_limit_reached(matches) {
+                return Ok(matches);
+            }
+        }
+    }
+
+    /// Counts the matching lines of `reader`, without reporting any of them.
+    ///
+    /// Context options are ignored; like `search_reader`, this runs in
+    /// constant memory, and counts at most `max_count` lines.
+    pub fn count<R: BufRead>(&self, matcher: &Matcher, mut reader: R) -> io::Result<u64> {
+        let mut count = 0;
+        if self.fixed_strings && !self.invert_match {
+            if !self.is_limit_reached(count) {
+                search_blocks(matcher, reader, |_| {
+                    count += 1;
+                    Ok(!self.is_limit_reached(count))
+                })?;
+            }
+            return Ok(count);
+        }
+
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            if self.is_limit_reached(count) || reader.read_until(b'\n', &mut buf)? == 0 {
+                return Ok(count);
+            }
+            if self.find(matcher, trim_line_terminator(&buf)).is_some() {
+                count += 1;
+            }
+        }
+    }
+
+    /// Returns whether any line of `reader` matches, stopping at the first one.
+    /// Nothing matches when `max_count` is zero.
+    pub fn has_match<R: BufRead>(&self, matcher: &Matcher, mut reader: R) -> io::Result<bool> {
+        if self.is_limit_reached(0) {
+            return Ok(false);
+        }
+        if self.fixed_strings && !self.invert_match {
+            let mut found = false;
+            search_blocks(matcher, reader, |_| {
+                found = true;
+                Ok(false)
+            })?;
+            return Ok(found);
+        }
+
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            if reader.read_until(b'\n', &mut buf)? == 0 {
+                return Ok(false);
+            }
+            if self.find(matcher, trim_line_terminator(&buf)).is_some() {
+                return Ok(true);
+            }
+        }
+    }
+
+    fn is_limit_reached(&self, matches: u64) -> bool {
+        self.max_count.is_some_and(|max| matches >= max)
+    }
+
+    /// Returns the span to report for `line`, if it should be reported as a match.
+    ///
+    /// Inverted matches have an empty span at the start of the line.
+    fn find(&self, matcher: &Matcher, line: &[u8]) -> Option<Range<usize>> {
+        if !self.invert_match {
+            matcher.find(line)
+        } else if matcher.is_match(line) {
+            None
+        } else {
+            Some(0..0)
+        }
+    }
+}
+
+/// Searches `reader` one line at a time, handing every matching line to `sink`.
+///
+/// This is `Searcher::search_reader` without any context lines or limit.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep_ag::{Config, LineMatch, Matcher};
+///
+/// let config = Config {
+///     patterns: vec!["the".to_string()],
+///     case_sensitive: true,
+///     ..Config::default()
+/// };
+/// let matcher = Matcher::new(&config).unwrap();
+/// let contents = "How public, like The Frog\nTo tell your name the livelong day\n";
+///
+/// let mut found = Vec::new();
+/// minigrep_ag::search_reader(&matcher, contents.as_bytes(), &mut |m: &LineMatch| {
+///     found.push((m.line_number, m.column(), String::from_utf8_lossy(m.line).into_owned()));
+///     Ok(true)
+/// })
+/// .unwrap();
+///
+/// assert_eq!(vec![(2, 19, "To tell your name the livelong day".to_string())], found);
+/// ```
+pub fn search_reader<R, S>(matcher: &Matcher, reader: R, sink: &mut S) -> io::Result<u64>
+where
+    R: BufRead,
+    S: Sink + ?Sized,
+{
+    Searcher::default().search_reader(matcher, reader, sink)
+}
+
+/// How much of the input `search_blocks` reads at a time, at least.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Searches `reader` a block at a time, handing every matching line to `matched`
+/// until it returns `false`.
+///
+/// Each block ends at a line boundary, with the partial line at its end kept
+/// for the next one. The matcher looks for candidates across the whole block,
+/// and only the lines around them are split out and checked. Lines are
+/// counted in bulk between hits.
+fn search_blocks<R, F>(matcher: &Matcher, mut reader: R, mut matched: F) -> io::Result<()>
+where
+    R: BufRead,
+    F: FnMut(&LineMatch) -> io::Result<bool>,
+{
+    let mut buf = Vec::with_capacity(BLOCK_SIZE);
+    // The number of lines before `counted`, an offset in `buf`.
+    let mut line_number = 0;
+    let mut counted = 0;
+    // The offset of the start of `buf` in the input.
+    let mut buf_offset = 0;
+
+    loop {
+        let len = buf.len();
+        buf.resize(len + BLOCK_SIZE, 0);
+        let read = loop {
+            match reader.read(&mut buf[len..]) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                result => break result,
+            }
+        }?;
+        buf.truncate(len + read);
+
+        let eof = read == 0;
+        let end = if eof {
+            buf.len()
+        } else {
+            // What was left from the last block has no line terminator.
+            match memrchr(b'\n', &buf[len..]) {
+                Some(i) => len + i + 1,
+                None => continue,
+            }
+        };
+
+        let mut pos = 0;
+        while pos < end {
+            let hit = match matcher.find_candidate(&buf[..end], pos) {
+                Some(hit) => hit,
+                None => break,
+            };
+            let start = memrchr(b'\n', &buf[..hit]).map_or(0, |i| i + 1);
+            let line_end = memchr(b'\n', &buf[hit..end]).map_or(end, |i| hit + i);
+
+            line_number += memchr_iter(b'\n', &buf[counted..start]).count() as u64;
+            counted = start;
+
+            let line = trim_line_terminator(&buf[start..line_end]);
+            if let Some(span) = matcher.find(line) {
+                let m = LineMatch {
+                    line,
+                    line_number: line_number + 1,
+                    byte_offset: buf_offset + start as u64,
+                    span,
+                };
+                if !matched(&m)? {
+                    return Ok(());
+                }
+            }
+            pos = line_end + 1;
+        }
+
+        if eof {
+            return Ok(());
+        }
+        line_number += memchr_iter(b'\n', &buf[counted..end]).count() as u64;
+        counted = 0;
+        buf_offset += end as u64;
+        buf.drain(..end);
+    }
+}
+
+fn trim_line_terminator(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stops_when_sink_says_so() {
+        let config = Config {
+            patterns: vec!["a".to_string()],
+            case_sensitive: true,
+            ..Config::default()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+        let contents = "a1\r\nb\nxa2\na3";
+
+        let mut found = Vec::new();
+        search_reader(&matcher, contents.as_bytes(), &mut |m: &LineMatch| {
+            found.push((m.line.to_vec(), m.line_number, m.byte_offset, m.column()));
+            Ok(found.len() < 2)
+        })
+        .unwrap();
+
+        assert_eq!(
+            vec![(b"a1".to_vec(), 1, 0, 1), (b"xa2".to_vec(), 3, 6, 2)],
+            found
+        );
+    }
+
+    /// Records every event as a line of text, grep style.
+    struct Recorder(Vec<String>);
+
+    impl Sink for Recorder {
+        fn matched(&mut self, m: &LineMatch) -> io::Result<bool> {
+            let line = String::from_utf8_lossy(m.line);
+            self.0.push(format!("{}:{}", m.line_number, line));
+            Ok(true)
+        }
+
+        fn context(&mut self, c: &ContextLine) -> io::Result<bool> {
+            let line = String::from_utf8_lossy(c.line);
+            self.0.push(format!("{}-{}", c.line_number, line));
+            Ok(true)
+        }
+
+        fn context_break(&mut self) -> io::Result<()> {
+            self.0.push("--".to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn inverted_matches() {
+        let config = Config {
+            patterns: vec!["X".to_string()],
+            invert_match: true,
+            ..Config::default()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+        let contents = "a\nx1\nb";
+
+        let mut recorder = Recorder(Vec::new());
+        Searcher::new(&config)
+            .search_reader(&matcher, contents.as_bytes(), &mut recorder)
+            .unwrap();
+
+        assert_eq!(vec!["1:a", "3:b"], recorder.0);
+    }
+
+    #[test]
+    fn counts_lines() {
+        let mut config = Config {
+            patterns: vec!["x".to_string()],
+            after_context: 2,
+            ..Config::default()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+        let contents = "x\na\nbx\nXX";
+
+        assert_eq!(
+            3,
+            Searcher::new(&config)
+                .count(&matcher, contents.as_bytes())
+                .unwrap()
+        );
+
+        config.invert_match = true;
+        assert_eq!(
+            1,
+            Searcher::new(&config)
+                .count(&matcher, contents.as_bytes())
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn max_count() {
+        let mut config = Config {
+            patterns: vec!["x".to_string()],
+            max_count: Some(2),
+            ..Config::default()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+        let contents = "x1\na\nx2\nx3\nb\nx4";
+
+        let search = |config: &Config| {
+            let mut recorder = Recorder(Vec::new());
+            let searcher = Searcher::new(config);
+            let matches = searcher
+                .search_reader(&matcher, contents.as_bytes(), &mut recorder)
+                .unwrap();
+            let count = searcher.count(&matcher, contents.as_bytes()).unwrap();
+            assert_eq!(matches, count);
+            recorder.0
+        };
+
+        assert_eq!(vec!["1:x1", "3:x2"], search(&config));
+        config.fixed_strings = true;
+        assert_eq!(vec!["1:x1", "3:x2"], search(&config));
+
+        config.after_context = 2;
+        assert_eq!(vec!["1:x1", "2-a", "3:x2", "4-x3", "5-b"], search(&config));
+
+        config.max_count = Some(0);
+        assert!(search(&config).is_empty());
+        assert!(!Searcher::new(&config)
+            .has_match(&matcher, contents.as_bytes())
+            .unwrap());
+    }
+
+    /// A reader that fails if read past its contents, to check that searches stop early.
+    struct Strict<'a>(&'a [u8]);
+
+    impl io::Read for Strict<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(io::Error::other("read past the end"));
+            }
+            let len = buf.len().min(self.0.len());
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn stops_reading_at_max_count() {
+        for fixed_strings in [false, true] {
+            let config = Config {
+                patterns: vec!["x".to_string()],
+                fixed_strings,
+                max_count: Some(1),
+                ..Config::default()
+            };
+            let matcher = Matcher::new(&config).unwrap();
+            let reader = io::BufReader::with_capacity(4, Strict(b"a\nx\n"));
+            let mut recorder = Recorder(Vec::new());
+
+            let matches = Searcher::new(&config)
+                .search_reader(&matcher, reader, &mut recorder)
+                .unwrap();
+            assert_eq!(1, matches);
+        }
+    }
+
+    #[test]
+    fn fixed_strings_across_blocks() {
+        let mut contents = String::new();
+        for i in 0..20_000 {
+            let ending = if i % 3 == 0 { "\r\n" } else { "\n" };
+            contents.push_str(&format!("line {} {}{}", i, "x".repeat(i % 7), ending));
+        }
+        contents.push_str("last line 7");
+
+        let search = |patterns: &[&str], fixed_strings| {
+            let config = Config {
+                patterns: patterns.iter().map(|p| p.to_string()).collect(),
+                case_sensitive: true,
+                fixed_strings,
+                ..Config::default()
+            };
+            let matcher = Matcher::new(&config).unwrap();
+            let searcher = Searcher::new(&config);
+            let mut found = Vec::new();
+            searcher
+                .search_reader(&matcher, contents.as_bytes(), &mut |m: &LineMatch| {
+                    found.push((
+                        m.line.to_vec(),
+                        m.line_number,
+                        m.byte_offset,
+                        m.span.clone(),
+                    ));
+                    Ok(true)
+                })
+                .unwrap();
+            let count = searcher.count(&matcher, contents.as_bytes()).unwrap();
+            assert_eq!(found.len() as u64, count);
+            found
+        };
+
+        for patterns in [&["7 x"][..], &["xxxxxx", "99"], &["7"], &[""], &["nothing"]] {
+            assert_eq!(search(patterns, false), search(patterns, true));
+        }
+        assert_eq!(20_001, search(&[""], true).len());
+    }
+
+    #[test]
+    fn context_lines() {
+        let config = Config {
+            patterns: vec!["x".to_string()],
+            case_sensitive: true,
+            before_context: 1,
+            after_context: 1,
+            ..Config::default()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+        let contents = "a\nx1\nb\nx2\nc\nd\ne\nx3\nf";
+
+        let mut recorder = Recorder(Vec::new());
+        Searcher::new(&config)
+            .search_reader(&matcher, contents.as_bytes(), &mut recorder)
+            .unwrap();
+
+        assert_eq!(
+            vec!["1-a", "2:x1", "3-b", "4:x2", "5-c", "--", "7-e", "8:x3", "9-f"],
+            recorder.0
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Named file types, for `-t` and `-T`.
+//!
+//! Each type is a set of globs matched against file names, such as `*.rs`
+//! for `rust`. The built-in types can be extended with `--type-add`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::glob::Glob;
+
+/// The built-in types, with their globs.
+const DEFAULT_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    (
+        "cpp",
+        &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx", "*.h"],
+    ),
+    ("csharp", &["*.cs"]),
+    ("css", &["*.css", "*.scss", "*.sass", "*.less"]),
+    ("docker", &["Dockerfile", "*.dockerfile"]),
+    ("go", &["*.go"]),
+    ("haskell", &["*.hs", "*.lhs"]),
+    ("html", &["*.htm", "*.html"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.jsx", "*.mjs", "*.cjs"]),
+    ("json", &["*.json"]),
+    ("kotlin", &["*.kt", "*.kts"]),
+    ("lua", &["*.lua"]),
+    ("make", &["Makefile", "makefile", "GNUmakefile", "*.mk"]),
+    ("markdown", &["*.md", "*.markdown"]),
+    ("php", &["*.php"]),
+    ("python", &["*.py", "*.pyi"]),
+    ("ruby", &["*.rb", "*.gemspec", "Gemfile", "Rakefile"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("sql", &["*.sql"]),
+    ("swift", &["*.swift"]),
+    ("toml", &["*.toml", "Cargo.lock"]),
+    ("ts", &["*.ts", "*.tsx", "*.mts", "*.cts"]),
+    ("txt", &["*.txt"]),
+    ("xml", &["*.xml"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+/// A table of file types, by name.
+#[derive(Debug, Clone)]
+pub struct FileTypes {
+    types: BTreeMap<String, Vec<Glob>>,
+}
+
+impl Default for FileTypes {
+    /// Returns the built-in types.
+    fn default() -> FileTypes {
+        let types = DEFAULT_TYPES
+            .iter()
+            .map(|(name, globs)| {
+                let globs = globs.iter().map(|glob| Glob::new(glob).unwrap()).collect();
+                (name.to_string(), globs)
+            })
+            .collect();
+        FileTypes { types }
+    }
+}
+
+impl FileTypes {
+    /// Adds a glob to a type, creating the type if needed, from a
+    /// definition such as `web:*.{html,css}`.
+    pub fn add(&mut self, definition: &str) -> Result<(), String> {
+        let (name, glob) = definition
+            .split_once(':')
+            .ok_or("expected a definition like 'name:glob'")?;
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!("invalid type name '{}'", name));
+        }
+
+        let glob = Glob::new(glob).map_err(|e| e.to_string())?;
+        self.types.entry(name.to_string()).or_default().push(glob);
+        Ok(())
+    }
+
+    /// Returns the globs of a type, if there is one with that name.
+    pub fn globs(&self, name: &str) -> Option<&[Glob]> {
+        self.types.get(name).map(Vec::as_slice)
+    }
+}
+
+impl fmt::Display for FileTypes {
+    /// Lists the types one per line, sorted by name, as `name: glob, glob`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, globs) in &self.types {
+            let globs: Vec<&str> = globs.iter().map(Glob::as_str).collect();
+            writeln!(f, "{}: {}", name, globs.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn globs<'a>(types: &'a FileTypes, name: &str) -> Vec<&'a str> {
+        types
+            .globs(name)
+            .unwrap()
+            .iter()
+            .map(Glob::as_str)
+            .collect()
+    }
+
+    #[test]
+    fn built_in_types() {
+        let types = FileTypes::default();
+
+        assert_eq!(vec!["*.rs"], globs(&types, "rust"));
+        assert_eq!(vec!["*.py", "*.pyi"], globs(&types, "python"));
+        assert!(types.globs("cobol").is_none());
+        assert!(types.to_string().contains("\npython: *.py, *.pyi\n"));
+    }
+
+    #[test]
+    fn added_types() {
+        let mut types = FileTypes::default();
+        types.add("web:*.{html,css}").unwrap();
+        types.add("rust:build.rs.in").unwrap();
+
+        assert_eq!(vec!["*.{html,css}"], globs(&types, "web"));
+        assert_eq!(vec!["*.rs", "build.rs.in"], globs(&types, "rust"));
+        assert!(types.add("web").is_err());
+        assert!(types.add(":*.x").is_err());
+        assert!(types.add("web:*.{x").is_err());
+    }
+}
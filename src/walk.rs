@@ -0,0 +1,320 @@
+//! Recursive directory walking.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::filter::PathFilter;
+use crate::ignore::Ignore;
+
+/// Options controlling which entries a `Walk` visits.
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    /// How many directory levels to descend below the root; `None` is unlimited.
+    pub max_depth: Option<usize>,
+    /// Follow symbolic links instead of skipping them.
+    pub follow_links: bool,
+    /// Skip files and directories whose name starts with a `.`.
+    pub skip_hidden: bool,
+    /// Skip the paths listed in `.ignore`, `.gitignore`, `.git/info/exclude`
+    /// and global git excludes files, starting from those of the git
+    /// repository holding the root, if any.
+    pub ignore: bool,
+    /// The contents of the global git excludes file, read once with
+    /// `ignore::global_excludes` for every walk, when ignore files are used.
+    pub global_excludes: Option<Arc<str>>,
+    /// Which files and directories to visit, by name or path. Unlike the
+    /// other options, it applies to the root too.
+    pub filter: PathFilter,
+}
+
+/// An iterator over every file under a root directory.
+///
+/// Entries of each directory are visited in file name order, so the output is
+/// the same from one run to the next. Errors reading a directory are yielded
+/// without stopping the walk. The root itself is only skipped by the filter.
+pub struct Walk {
+    options: WalkOptions,
+    root: PathBuf,
+    /// Paths waiting to be visited, with their depth below the root and the
+    /// ignore files of their directory.
+    stack: Vec<(PathBuf, usize, Option<Arc<Ignore>>)>,
+    /// Canonical paths of the directories already entered, to break symlink loops.
+    visited: HashSet<PathBuf>,
+    /// The canonical path of the root, which ignore files are matched against.
+    absolute_root: PathBuf,
+}
+
+impl Walk {
+    pub fn new(root: &Path, options: WalkOptions) -> Walk {
+        Walk {
+            options,
+            root: root.to_path_buf(),
+            stack: vec![(root.to_path_buf(), 0, None)],
+            visited: HashSet::new(),
+            absolute_root: PathBuf::new(),
+        }
+    }
+
+    /// Pushes the entries of `dir` onto the stack, in reverse so they pop in order.
+    fn push_children(
+        &mut self,
+        dir: &Path,
+        depth: usize,
+        parent: Option<Arc<Ignore>>,
+    ) -> io::Result<()> {
+        if self.options.follow_links && !self.visited.insert(dir.canonicalize()?) {
+            return Ok(());
+        }
+
+        let ignore = if self.options.ignore {
+            let parent = if depth == 0 {
+                self.absolute_root = dir.canonicalize()?;
+                self.repository_ignore()
+            } else {
+                parent
+            };
+            let dir = self.absolute(dir);
+            Some(Arc::new(Ignore::new(
+                parent,
+                &dir,
+                self.options.global_excludes.as_deref(),
+            )))
+        } else {
+            None
+        };
+
+        let mut children = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if self.options.skip_hidden && is_hidden(&path) {
+                continue;
+            }
+            let is_dir = if self.options.follow_links {
+                path.is_dir()
+            } else {
+                entry.file_type()?.is_dir()
+            };
+            if is_dir && self.options.filter.is_dir_excluded(&path) {
+                continue;
+            }
+            if let Some(ignore) = &ignore {
+                if ignore.is_ignored(&self.absolute(&path), is_dir) {
+                    continue;
+                }
+            }
+            children.push(path);
+        }
+        children.sort();
+
+        self.stack.extend(
+            children
+                .into_iter()
+                .rev()
+                .map(|path| (path, depth + 1, ignore.clone())),
+        );
+        Ok(())
+    }
+
+    /// Reads the ignore files of the directories above the root, up to the
+    /// root of the git repository holding it. Outside of a repository, only
+    /// the ignore files below the root apply.
+    fn repository_ignore(&self) -> Option<Arc<Ignore>> {
+        let parent = self.absolute_root.parent()?;
+        let repository = parent
+            .ancestors()
+            .position(|dir| dir.join(".git").exists())?;
+
+        let dirs: Vec<&Path> = parent.ancestors().take(repository + 1).collect();
+        dirs.into_iter().rev().fold(None, |ignore, dir| {
+            Some(Arc::new(Ignore::new(
+                ignore,
+                dir,
+                self.options.global_excludes.as_deref(),
+            )))
+        })
+    }
+
+    /// Returns the path of an entry below the root, starting from the
+    /// canonical path of the root.
+    fn absolute(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) if relative.as_os_str().is_empty() => self.absolute_root.clone(),
+            Ok(relative) => self.absolute_root.join(relative),
+            Err(_) => path.to_path_buf(),
+        }
+    }
+}
+
+impl Iterator for Walk {
+    type Item = io::Result<PathBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((path, depth, ignore)) = self.stack.pop() {
+            let metadata = if depth == 0 || self.options.follow_links {
+                fs::metadata(&path)
+            } else {
+                fs::symlink_metadata(&path)
+            };
+            let file_type = match metadata {
+                Ok(metadata) => metadata.file_type(),
+                Err(e) => return Some(Err(with_path(e, &path))),
+            };
+
+            if file_type.is_dir() {
+                if depth == 0 && self.options.filter.is_dir_excluded(&path) {
+                    continue;
+                }
+                if self.options.max_depth.is_none_or(|max| depth < max) {
+                    if let Err(e) = self.push_children(&path, depth, ignore) {
+                        return Some(Err(with_path(e, &path)));
+                    }
+                }
+            } else if file_type.is_file() && self.options.filter.is_file_included(&path) {
+                return Some(Ok(path));
+            }
+        }
+
+        None
+    }
+}
+
+/// Returns whether the last component of `path` is a hidden name.
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+/// Prefixes an I/O error with the path it happened on.
+fn with_path(error: io::Error, path: &Path) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {}", path.display(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("minigrep-walk-{}", name));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join("top.txt"), "").unwrap();
+        fs::write(root.join("src/lib.rs"), "").unwrap();
+        fs::write(root.join("src/nested/deep.rs"), "").unwrap();
+        fs::write(root.join(".git/config"), "").unwrap();
+        root
+    }
+
+    fn walk(root: &Path, options: WalkOptions) -> Vec<String> {
+        Walk::new(root, options)
+            .map(|path| {
+                let path = path.unwrap();
+                let relative = path.strip_prefix(root).unwrap();
+                relative.to_string_lossy().replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn walks_in_order() {
+        let root = tree("order");
+
+        assert_eq!(
+            vec![".git/config", "src/lib.rs", "src/nested/deep.rs", "top.txt"],
+            walk(&root, WalkOptions::default())
+        );
+    }
+
+    #[test]
+    fn max_depth_and_hidden() {
+        let root = tree("depth");
+        let options = WalkOptions {
+            max_depth: Some(2),
+            skip_hidden: true,
+            ..WalkOptions::default()
+        };
+
+        assert_eq!(vec!["src/lib.rs", "top.txt"], walk(&root, options));
+    }
+
+    #[test]
+    fn ignore_files() {
+        let root = tree("ignore");
+        fs::create_dir_all(root.join(".git/info")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        let files = [
+            (".gitignore", "*.log\ntarget/\n/top.txt\n"),
+            (".ignore", "!keep.log\n"),
+            (".git/info/exclude", "secret.txt\n"),
+            ("src/.gitignore", "!b.log\nnested/\n"),
+            ("a.log", ""),
+            ("keep.log", ""),
+            ("secret.txt", ""),
+            ("target/debug.rs", ""),
+            ("src/b.log", ""),
+            ("src/c.log", ""),
+        ];
+        for (path, contents) in files {
+            fs::write(root.join(path), contents).unwrap();
+        }
+        let options = WalkOptions {
+            skip_hidden: true,
+            ignore: true,
+            ..WalkOptions::default()
+        };
+
+        assert_eq!(
+            vec!["keep.log", "src/b.log", "src/lib.rs"],
+            walk(&root, options.clone())
+        );
+        // The files of the directories above the root apply too.
+        assert_eq!(
+            vec!["b.log", "lib.rs"],
+            walk(&root.join("src"), options.clone())
+        );
+
+        let options = WalkOptions {
+            global_excludes: Some(Arc::from("*.rs\n")),
+            ..options
+        };
+        assert_eq!(vec!["keep.log", "src/b.log"], walk(&root, options));
+    }
+
+    #[test]
+    fn filters() {
+        let root = tree("filter");
+        let glob = |pattern| crate::glob::Glob::new(pattern).unwrap();
+        let options = WalkOptions {
+            filter: PathFilter {
+                include: vec![glob("*.rs")],
+                exclude_dir: vec![glob("nested")],
+                ..PathFilter::default()
+            },
+            ..WalkOptions::default()
+        };
+
+        assert_eq!(vec!["src/lib.rs"], walk(&root, options.clone()));
+        assert!(walk(&root.join("top.txt"), options.clone()).is_empty());
+        assert!(walk(&root.join("src/nested"), options).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loops() {
+        let root = tree("links");
+        std::os::unix::fs::symlink(&root, root.join("src/loop")).unwrap();
+
+        assert_eq!(4, walk(&root, WalkOptions::default()).len());
+
+        let follow = WalkOptions {
+            follow_links: true,
+            ..WalkOptions::default()
+        };
+        assert_eq!(4, walk(&root, follow).len());
+    }
+}